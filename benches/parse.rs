@@ -0,0 +1,83 @@
+//! Timing comparisons for the parsing entry points, run with `cargo bench`.
+//!
+//! This uses a plain timing loop rather than the unstable `test` crate, so
+//! it builds on stable Rust without extra dependencies.
+
+extern crate foropts;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 100_000;
+
+#[allow(dead_code)]
+enum Opt {
+    Verbose,
+    Level(u8),
+    File(String),
+}
+
+fn config() -> foropts::Config<'static, Opt> {
+    foropts::Config::new("bench")
+        .arg(foropts::Arg::flag(|| Opt::Verbose).short('v').long("verbose"))
+        .arg(foropts::Arg::parsed_param("LEVEL", Opt::Level).short('l').long("level"))
+        .arg(foropts::Arg::parsed_param("FILE", Opt::File))
+}
+
+fn args() -> Vec<String> {
+    ["-v", "--level=3", "a.txt", "-vl", "7", "b.txt", "--verbose"]
+        .iter().map(ToString::to_string).collect()
+}
+
+/// Runs `f` `ITERATIONS` times and prints the average time per run.
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    let start = Instant::now();
+    for _ in 0 .. ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    let per_run = elapsed / ITERATIONS;
+    println!("{:<24} {:>10?} per run", name, per_run.max(Duration::from_nanos(1)));
+}
+
+fn bench_parse_into() {
+    let config = config();
+    let args   = args();
+
+    bench("iter + collect", || {
+        let result: foropts::Result<Vec<Opt>> = config.iter(args.clone()).collect();
+        black_box(result.unwrap());
+    });
+
+    let mut out = Vec::new();
+    bench("parse_into (reused Vec)", || {
+        config.parse_into(args.clone(), &mut out, true).unwrap();
+        black_box(&out);
+    });
+}
+
+fn bench_parse_shorts() {
+    let spec = ['a', 'b', 'c', 'd'];
+    let args = ["-abc", "-d", "x", "-ca", "y"];
+
+    bench("parse_shorts", || {
+        black_box(foropts::parse_shorts(&spec, &args).unwrap());
+    });
+
+    let mut config = foropts::Config::new("bench");
+    for &c in &spec {
+        config = config.arg(foropts::Arg::flag(move || Some(c)).short(c));
+    }
+    let config = config.arg(foropts::Arg::str_param("ARG", |_| Ok(None)));
+
+    bench("generic shorts", || {
+        let args = args.iter().map(ToString::to_string);
+        let result: foropts::Result<Vec<Option<char>>> = config.iter(args).collect();
+        black_box(result.unwrap());
+    });
+}
+
+fn main() {
+    bench_parse_into();
+    bench_parse_shorts();
+}
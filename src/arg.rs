@@ -1,7 +1,14 @@
 use super::*;
 use util::*;
 
-use std::{fmt, io};
+use std::{any, env, fmt, io};
+use std::ffi::OsStr;
+use std::rc::Rc;
+
+type Action<'a, T> = Box<dyn Fn(&str) -> Result<T> + 'a>;
+type BareAction<'a, T> = Box<dyn Fn() -> Result<T> + 'a>;
+type CountAction<'a, T> = Box<dyn Fn(usize) -> T + 'a>;
+type OsAction<'a, T> = Box<dyn Fn(&OsStr) -> Result<T> + 'a>;
 
 /// A description of an argument, which may be a Boolean flag or carry a parameter.
 ///
@@ -12,10 +19,28 @@ use std::{fmt, io};
 /// `<T>`  – The result type of the argument
 pub struct Arg<'a, T> {
     name:       String,
-    action:     Box<Fn(&str) -> Result<T> + 'a>,
+    action:     Action<'a, T>,
+    bare:       Option<BareAction<'a, T>>,
+    plus:       Option<BareAction<'a, T>>,
+    count:      Option<CountAction<'a, T>>,
+    os_action:  Option<OsAction<'a, T>>,
     short:      Option<char>,
+    short_aliases: Vec<char>,
     long:       String,
+    long_aliases: Vec<String>,
     descr:      String,
+    ignored:    bool,
+    single:     bool,
+    greedy:     bool,
+    required:   bool,
+    default:    Option<String>,
+    conflicts:  Vec<String>,
+    requires:   Vec<String>,
+    values:     Vec<String>,
+    group:      Option<String>,
+    type_name:  Option<String>,
+    min_count:  usize,
+    max_count:  Option<usize>,
 }
 
 impl<'a, T> fmt::Debug for Arg<'a, T> {
@@ -23,9 +48,27 @@ impl<'a, T> fmt::Debug for Arg<'a, T> {
         f.debug_struct("Arg")
             .field("name",      &self.name)
             .field("action",    &"…")
+            .field("bare",      &self.bare.as_ref().map(|_| "…"))
+            .field("plus",      &self.plus.as_ref().map(|_| "…"))
+            .field("count",     &self.count.as_ref().map(|_| "…"))
+            .field("os_action", &self.os_action.as_ref().map(|_| "…"))
             .field("short",     &self.short)
+            .field("short_aliases", &self.short_aliases)
             .field("long",      &self.long)
+            .field("long_aliases", &self.long_aliases)
             .field("descr",     &self.descr)
+            .field("ignored",   &self.ignored)
+            .field("single",    &self.single)
+            .field("greedy",    &self.greedy)
+            .field("required",  &self.required)
+            .field("default",   &self.default)
+            .field("conflicts", &self.conflicts)
+            .field("requires",  &self.requires)
+            .field("values",    &self.values)
+            .field("group",     &self.group)
+            .field("type_name", &self.type_name)
+            .field("min_count", &self.min_count)
+            .field("max_count", &self.max_count)
             .finish()
     }
 }
@@ -38,6 +81,36 @@ impl<'a, T> Arg<'a, T> {
         Self::str_param("", move |_| Ok(thunk()))
     }
 
+    /// Creates a new toggle, which is switched on by `+x` and off by `-x`
+    /// when [`Config::plus_minus_pairs`](struct.Config.html#method.plus_minus_pairs)
+    /// is enabled. The action receives `true` for `+x` and `false` for `-x`.
+    /// A long name, if given, acts like `+x`.
+    pub fn toggle<F>(action: F) -> Self
+        where F: Fn(bool) -> T + 'a
+    {
+        let action = Rc::new(action);
+        let on     = action.clone();
+        let mut arg = Self::flag(move || action(false));
+        arg.plus = Some(Box::new(move || Ok(on(true))));
+        arg
+    }
+
+    /// Creates a new flag that yields a single result carrying the number of
+    /// times it appeared, as for `-vvv`. Occurrences are counted across the
+    /// whole command line, so `-vv -v` also counts 3, and the result comes
+    /// once the arguments run out. If the flag never appears, nothing is
+    /// yielded. A [`Cursor`](struct.Cursor.html) parses each occurrence
+    /// separately, as a count of 1.
+    pub fn counted<F>(action: F) -> Self
+        where F: Fn(usize) -> T + 'a
+    {
+        let action  = Rc::new(action);
+        let single  = action.clone();
+        let mut arg = Self::flag(move || single(1));
+        arg.count = Some(Box::new(move |n| action(n)));
+        arg
+    }
+
     /// Creates a new argument with raw string parameter.
     ///
     /// # Parameters
@@ -57,13 +130,34 @@ impl<'a, T> Arg<'a, T> {
         Arg {
             name:       name.into(),
             action:     Box::new(parser),
+            bare:       None,
+            plus:       None,
+            count:      None,
+            os_action:  None,
             short:      None,
+            short_aliases: Vec::new(),
             long:       String::new(),
+            long_aliases: Vec::new(),
             descr:      String::new(),
+            ignored:    false,
+            single:     false,
+            greedy:     false,
+            required:   false,
+            default:    None,
+            conflicts:  Vec::new(),
+            requires:   Vec::new(),
+            values:     Vec::new(),
+            group:      None,
+            type_name:  None,
+            min_count:  0,
+            max_count:  None,
         }
     }
 
     /// Creates a new argument with a parameter parsed by `str::parse`.
+    /// When parsing fails, the error names the parameter, its type and the
+    /// value, as in `option -p: invalid value for <PORT:u16>: 'x'`; see
+    /// [`type_name`](#method.type_name).
     ///
     /// # Parameters
     ///
@@ -82,10 +176,145 @@ impl<'a, T> Arg<'a, T> {
               A: FromStr,
               A::Err: ToString
     {
-        Arg::str_param(name, move |slice|
+        let mut arg = Arg::str_param(name, move |slice|
             slice.parse()
                 .map(&wrapper)
-                .map_err(|s| Error::from_string(&s)))
+                .map_err(|e: A::Err| Error::from_string(&e.to_string())
+                    .with_kind(ErrorKind::ParseFailure)));
+        arg.type_name = Some(short_type_name(any::type_name::<A>()));
+        arg
+    }
+
+    /// Creates a new argument whose parameter is a key and a value separated
+    /// by `sep`, as in `-D key=value`. The parameter is split at the first
+    /// `sep` and both parts are passed to `action`. A parameter without `sep`
+    /// is an error.
+    ///
+    /// # Parameters
+    ///
+    /// `<S>` – type converted to `String` to name the parameter
+    ///
+    /// `<F>` – type of the action
+    ///
+    /// `name` – the name of the parameter
+    ///
+    /// `sep` – the separator between key and value
+    ///
+    /// `action` – function applied to the key and value
+    pub fn key_value<S, F>(name: S, sep: char, action: F) -> Self
+        where S: Into<String>,
+              F: Fn(String, String) -> T + 'a
+    {
+        Arg::str_param(name, move |slice| {
+            let ix = slice.find(sep).ok_or_else(|| Error::from_string(
+                &format!("expected KEY{}VALUE", sep)))?;
+            Ok(action(slice[.. ix].to_owned(), slice[ix + sep.len_utf8() ..].to_owned()))
+        })
+    }
+
+    /// Creates a new argument whose parameter is passed to `parser` as an
+    /// `OsStr`. When parsing with
+    /// [`Config::iter_os`](struct.Config.html#method.iter_os), a parameter
+    /// that is not valid Unicode, such as a path on Unix, reaches `parser`
+    /// unchanged. (Wrappers that work on the parameter as a string, such as
+    /// [`expand_env`](#method.expand_env), see only parameters that are
+    /// valid Unicode.)
+    ///
+    /// # Parameters
+    ///
+    /// `<S>` – type converted to `String` to name the parameter
+    ///
+    /// `<F>` – type of parsing function
+    ///
+    /// `name` – the name of the parameter
+    ///
+    /// `parser` – the parsing function, which must convert the raw
+    /// `&OsStr` to a `Result<T>`
+    pub fn os_param<S, F>(name: S, parser: F) -> Self
+        where S: Into<String>,
+              F: Fn(&OsStr) -> Result<T> + 'a
+    {
+        let parser  = Rc::new(parser);
+        let on_str  = parser.clone();
+        let mut arg = Arg::str_param(name, move |slice| on_str(OsStr::new(slice)));
+        arg.os_action = Some(Box::new(move |raw| parser(raw)));
+        arg
+    }
+
+    /// Creates a new argument whose parameter is optional, with separate
+    /// actions for when it is given and when it is not.
+    ///
+    /// The parameter must be attached to the option, as in `-c9` or
+    /// `--compress=9`; a bare `-c` or `--compress` does not consume the
+    /// following argument.
+    ///
+    /// # Parameters
+    ///
+    /// `<S>` – type converted to `String` to name the parameter
+    ///
+    /// `<F>` – type of the action for the bare option
+    ///
+    /// `<G>` – type of parsing function for the parameter
+    ///
+    /// `name` – the name of the parameter
+    ///
+    /// `bare` – the action used when no parameter is attached
+    ///
+    /// `parser` – the parsing function, which must convert the raw
+    /// `&str` to a `Result<T>`
+    pub fn optional_param<S, F, G>(name: S, bare: F, parser: G) -> Self
+        where S: Into<String>,
+              F: Fn() -> T + 'a,
+              G: Fn(&str) -> Result<T> + 'a
+    {
+        let mut arg = Arg::str_param(name, parser);
+        arg.bare = Some(Box::new(move || Ok(bare())));
+        arg
+    }
+
+    /// Creates an option from a spec such as `-o, --output <FILE>`, which
+    /// gives its short name, long name and parameter name, in any order and
+    /// separated by spaces or commas. The option takes a parameter if and
+    /// only if the spec names one; a flag’s action receives `""`.
+    pub (crate) fn from_spec<F>(spec: &str, action: F) -> Result<Self>
+        where F: Fn(&str) -> Result<T> + 'a
+    {
+        let invalid = || Error::from_string(&format!("invalid option spec: {:?}", spec));
+
+        let mut short = None;
+        let mut long  = None;
+        let mut name  = None;
+
+        for token in spec.split(|c: char| c == ',' || c.is_whitespace()) {
+            let slot = if token.is_empty() {
+                continue;
+            } else if token.starts_with('<') && token.ends_with('>') && token.len() > 2 {
+                name.replace(&token[1 .. token.len() - 1])
+            } else if let Some(rest) = token.strip_prefix("--") {
+                if rest.is_empty() || rest.starts_with('-') { return Err(invalid()); }
+                long.replace(rest)
+            } else if let Some(('-', rest)) = split_first_str(token) {
+                match split_first_str(rest) {
+                    Some((c, "")) if c != '-' => short.replace(rest),
+                    _                         => return Err(invalid()),
+                }
+            } else {
+                return Err(invalid());
+            };
+
+            if slot.is_some() {
+                return Err(invalid());
+            }
+        }
+
+        if short.is_none() && long.is_none() {
+            return Err(invalid());
+        }
+
+        let mut arg = Arg::str_param(name.unwrap_or(""), action);
+        arg.short = short.and_then(|s| s.chars().next());
+        arg.long  = long.unwrap_or("").to_owned();
+        Ok(arg)
     }
 
     /// Sets the short name of the option.
@@ -95,73 +324,445 @@ impl<'a, T> Arg<'a, T> {
         self
     }
 
+    /// Adds an alternative short name for the option, as in `-?` for `-h`.
+    /// Usage information shows only the main short name.
+    pub fn short_alias(self, c: char) -> Self {
+        self.short_aliases(&[c])
+    }
+
+    /// Adds alternative short names for the option; see
+    /// [`short_alias`](#method.short_alias).
+    pub fn short_aliases(mut self, cs: &[char]) -> Self {
+        for &c in cs {
+            assert_ne!( c, '-' , "Arg::short_aliases: c cannot be '-'" );
+        }
+        self.short_aliases.extend_from_slice(cs);
+        self
+    }
+
+    /// Sets the type shown in errors for a parameter that does not parse, as
+    /// in `invalid value for <RATIO:number>: 'x'`. An argument created by
+    /// [`parsed_param`](#method.parsed_param) shows the parsed type by
+    /// default; for others, this applies to errors from the action whose
+    /// kind is [`ParseFailure`](enum.ErrorKind.html#variant.ParseFailure).
+    pub fn type_name(mut self, name: &str) -> Self {
+        self.type_name = Some(name.to_owned());
+        self
+    }
+
     /// Sets the long name of the option.
     pub fn long<S: Into<String>>(mut self, s: S) -> Self {
         self.long = s.into();
         self
     }
 
+    /// Adds an alternative long name for the option, as in
+    /// `.long("color").alias("colour")`. Usage information lists it after
+    /// the main long name.
+    pub fn alias<S: Into<String>>(mut self, s: S) -> Self {
+        self.long_aliases.push(s.into());
+        self
+    }
+
+    /// Adds alternative long names for the option; see
+    /// [`alias`](#method.alias).
+    pub fn aliases(mut self, names: &[&str]) -> Self {
+        self.long_aliases.extend(names.iter().map(|&s| s.to_owned()));
+        self
+    }
+
+    /// Marks the option as ignored: it is recognized and consumes its
+    /// parameter as usual, but the iterator skips over it rather than yielding
+    /// its result. This is useful for options accepted only for compatibility.
+    pub fn ignored(mut self) -> Self {
+        self.ignored = true;
+        self
+    }
+
+    /// Marks the option as one that should be given at most once. Each
+    /// occurrence is still yielded, but a repeat records a warning, available
+    /// from [`Iter::warnings`](struct.Iter.html#method.warnings).
+    pub fn single(mut self) -> Self {
+        self.single = true;
+        self
+    }
+
+    /// Declares that the option cannot be combined with the option whose
+    /// long name (or long alias) is `long`. Whichever of the two appears
+    /// second is an error naming the first, as in
+    /// `option --quiet: conflicts with --verbose`. Declaring the conflict on
+    /// either option is enough.
+    pub fn conflicts_with(mut self, long: &str) -> Self {
+        self.conflicts.push(long.to_owned());
+        self
+    }
+
+    /// Declares that using the option requires also using the option whose
+    /// long name (or long alias) is `long`, in any position. This is checked
+    /// once the arguments run out, giving an error like
+    /// `option --commit: requires --message`.
+    pub fn requires(mut self, long: &str) -> Self {
+        self.requires.push(long.to_owned());
+        self
+    }
+
+    /// Marks the option as required. If it never appears, then once the
+    /// arguments run out the iterator yields the error
+    /// `required but not supplied` for it.
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Sets a parameter to use when the option never appears. Once the
+    /// arguments run out, the iterator passes `value` to the action and
+    /// yields the result, or the error if `value` does not parse. An option
+    /// with a default is never reported as missing, even if it is
+    /// [`required`](#method.required).
+    pub fn default_value<S: Into<String>>(mut self, value: S) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// Makes an empty parameter, as in `--include=`, produce the result of
+    /// `clear` rather than running the action. This lets an accumulating
+    /// option be reset partway through the arguments.
+    pub fn with_clear_on_empty<F>(self, clear: F) -> Self
+        where F: Fn() -> T + 'a,
+              T: 'a
+    {
+        self.wrap_action(move |param, action| {
+            if param.is_empty() {
+                Ok(clear())
+            } else {
+                action(param)
+            }
+        })
+    }
+
+    /// Makes the option, which must take a parameter, keep taking values
+    /// from the following arguments until one looks like an option, as in
+    /// `--files a b c --verbose`. Each value is parsed and yielded
+    /// separately, and the end-of-options marker also ends the values.
+    pub fn values_until_flag(mut self) -> Self {
+        self.greedy = true;
+        self
+    }
+
+    /// Expands environment variable references (`$VAR` or `${VAR}`) in the
+    /// parameter before passing it to the action; `$$` stands for a literal
+    /// `$`.
+    ///
+    /// If `unset_is_error` is true then referring to an unset variable is an
+    /// error; otherwise it expands to the empty string.
+    pub fn expand_env(self, unset_is_error: bool) -> Self
+        where T: 'a
+    {
+        self.expand_env_with(unset_is_error, |name| env::var(name).ok())
+    }
+
+    /// Like [`expand_env`](#method.expand_env), but looks up variables using
+    /// the given function rather than the process environment.
+    pub fn expand_env_with<F>(self, unset_is_error: bool, lookup: F) -> Self
+        where F: Fn(&str) -> Option<String> + 'a,
+              T: 'a
+    {
+        self.wrap_action(move |param, action| {
+            let expanded = expand_vars(param, |name| {
+                lookup(name).or_else(|| if unset_is_error {None} else {Some(String::new())})
+            }).map_err(|name| Error::from_string(
+                &format!("environment variable not set: {}", name)))?;
+            action(&expanded)
+        })
+    }
+
+    /// Rejects parameters for which `predicate` returns false, failing with
+    /// `message`. The check runs before the action, so it suits conditions
+    /// that a fixed list of values cannot express, such as “must be an
+    /// existing file”.
+    pub fn value_predicate<F, S>(self, predicate: F, message: S) -> Self
+        where F: Fn(&str) -> bool + 'a,
+              S: Into<String>,
+              T: 'a
+    {
+        let message = message.into();
+        self.wrap_action(move |param, action| {
+            if predicate(param) {
+                action(param)
+            } else {
+                Err(Error::from_string(&message))
+            }
+        })
+    }
+
+    /// Rejects parameters other than those in `values`, as in
+    /// `'sometimes' is not one of auto, always, never`. The check runs on the
+    /// parameter string before the action. The values are also listed in
+    /// the [`CompletionModel`](struct.CompletionModel.html).
+    pub fn possible_values(mut self, values: &[&str]) -> Self
+        where T: 'a
+    {
+        self.values = values.iter().map(|&v| v.to_owned()).collect();
+        let values  = self.values.clone();
+        self.wrap_action(move |param, action| {
+            if values.iter().any(|v| v == param) {
+                action(param)
+            } else {
+                Err(Error::from_string(
+                    &format!("'{}' is not one of {}", param, values.join(", "))))
+            }
+        })
+    }
+
+    /// For a positional argument, sets how many arguments it must take. If
+    /// it takes fewer, then once the arguments run out the iterator yields
+    /// the error `too few arguments (min N)` naming it.
+    pub fn min_count(mut self, min: usize) -> Self {
+        self.min_count = min;
+        self
+    }
+
+    /// For a positional argument, sets how many arguments it takes before
+    /// the next positional takes over. By default, each positional takes one
+    /// argument and the last takes all that remain. Once the last positional
+    /// has taken its maximum, each further positional argument is the error
+    /// `unexpected argument 'X': too many arguments (max N)`, reported where
+    /// it appears.
+    pub fn max_count(mut self, max: usize) -> Self {
+        self.max_count = Some(max);
+        self
+    }
+
+    /// Puts the option under the heading `group` in usage information, as in
+    /// `Output options:`, rather than under `OPTIONS:`. Groups appear in the
+    /// order of their first options.
+    pub fn group<S: Into<String>>(mut self, group: S) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
     /// Sets the description of the option (for the help message).
     pub fn description<S: Into<String>>(mut self, s: S) -> Self {
         self.descr = s.into();
         self
     }
 
-    pub (crate) fn new_error(&self, long: bool, msg: &str) -> Error {
-        let opt_name = if long {
-            format!("--{}", self.long)
-        } else if let Some(c) = self.short {
-            format!("-{}", c)
-        } else {
-            "-?".to_owned()
-        };
+    /// Replaces the action with `wrapper`, which receives the parameter and
+    /// the previous action.
+    fn wrap_action<F>(mut self, wrapper: F) -> Self
+        where F: Fn(&str, &dyn Fn(&str) -> Result<T>) -> Result<T> + 'a,
+              T: 'a
+    {
+        let action = self.action;
+        self.action = Box::new(move |param| wrapper(param, &*action));
+        self
+    }
 
-        Error::from_string(msg).with_option(opt_name)
+    /// Creates a flag whose action is never run, because the configuration
+    /// handles it before that.
+    pub (crate) fn reserved() -> Self {
+        Self::str_param("", |_| Err(Error::from_string("reserved option")))
     }
 
     /// Writes the usage for this option to the writer.
-    pub (crate) fn write_option_usage<W: io::Write>(&self, mut out: W) -> io::Result<()> {
+    pub (crate) fn write_option_usage<W: io::Write>(&self, out: W, width: usize, color: bool)
+        -> io::Result<()>
+    {
         if self.is_positional() { return Ok(()); }
 
+        let mut names = Vec::new();
         if let Some(c) = self.short {
+            names.push(format!("-{}", c));
+            if self.is_toggle() {
+                names.push(format!("+{}", c));
+            }
+        }
+        names.extend(self.get_longs().map(|s| format!("--{}", s)));
+        let names = names.join(", ");
+
+        let param = if self.takes_optional_parameter() {
             if self.long.is_empty() {
-                write!(out, "  -{}", c)?;
+                format!("[{}]", self.name)
             } else {
-                write!(out, "  -{}, --{}", c, self.long)?;
+                format!("[={}]", self.name)
             }
+        } else if !self.name.is_empty() {
+            format!(" <{}>", self.name)
         } else {
-            write!(out, "  --{}", self.long)?;
-        }
+            String::new()
+        };
 
-        if !self.name.is_empty() {
-            write!(out, " <{}>", self.name)?;
-        }
+        let heading = format!("  {}{}", names, param);
+        let shown   = format!("  {}{}", styled(&names, BOLD, color), param);
+        self.write_described(out, &heading, &shown, width)
+    }
+
+    /// Writes the usage for this positional argument to the writer.
+    pub (crate) fn write_positional_usage<W: io::Write>(&self, out: W, width: usize)
+        -> io::Result<()>
+    {
+        let heading = format!("  {}", self.positional_name());
+        self.write_described(out, &heading, &heading, width)
+    }
+
+    /// Writes `shown`, which is `heading` possibly with escape codes added,
+    /// followed by the description, wrapped to `width` columns with
+    /// continuation lines aligned under its first line.
+    fn write_described<W: io::Write>(&self, mut out: W, heading: &str, shown: &str,
+                                     width: usize)
+        -> io::Result<()>
+    {
+        write!(out, "{}", shown)?;
 
         if !self.descr.is_empty() {
-            write!(out, "   {}", self.descr)?;
+            let indent = heading.chars().count() + 3;
+            let lines  = wrap_words(&self.descr, width.saturating_sub(indent));
+            write!(out, "   {}", lines.join(&format!("\n{:1$}", "", indent)))?;
         }
 
         writeln!(out)
     }
 
     pub (crate) fn is_positional(&self) -> bool {
-        self.short.is_none() && self.long.is_empty()
+        self.short.is_none() && self.short_aliases.is_empty()
+            && self.long.is_empty() && self.long_aliases.is_empty()
+    }
+
+    /// Compares everything but the actions.
+    pub (crate) fn structurally_eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.bare.is_some() == other.bare.is_some()
+            && self.plus.is_some() == other.plus.is_some()
+            && self.count.is_some() == other.count.is_some()
+            && self.os_action.is_some() == other.os_action.is_some()
+            && self.short == other.short
+            && self.short_aliases == other.short_aliases
+            && self.long == other.long
+            && self.long_aliases == other.long_aliases
+            && self.descr == other.descr
+            && self.ignored == other.ignored
+            && self.single == other.single
+            && self.greedy == other.greedy
+            && self.required == other.required
+            && self.default == other.default
+            && self.conflicts == other.conflicts
+            && self.requires == other.requires
+            && self.values == other.values
+            && self.group == other.group
+            && self.type_name == other.type_name
+            && self.min_count == other.min_count
+            && self.max_count == other.max_count
+    }
+
+    /// The name of the option for messages, preferring the long name.
+    pub (crate) fn option_name(&self) -> String {
+        match self.short {
+            Some(c) if self.long.is_empty() => format!("-{}", c),
+            _                               => format!("--{}", self.long),
+        }
+    }
+
+    /// Whether this option and `other` were declared to conflict, in either
+    /// direction.
+    pub (crate) fn conflicts_with_arg(&self, other: &Self) -> bool {
+        let declared = |a: &Self, b: &Self|
+            a.conflicts.iter().any(|c| b.get_longs().any(|long| long == c));
+        declared(self, other) || declared(other, self)
+    }
+
+    /// The long names of the options that this option requires.
+    pub (crate) fn get_requires(&self) -> &[String] {
+        &self.requires
+    }
+
+    /// The values to which the parameter is restricted, if any.
+    pub (crate) fn get_possible_values(&self) -> &[String] {
+        &self.values
+    }
+
+    pub (crate) fn get_group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    pub (crate) fn is_single(&self) -> bool {
+        self.single
+    }
+
+    pub (crate) fn takes_values_until_flag(&self) -> bool {
+        self.greedy && self.takes_parameter()
+    }
+
+    pub (crate) fn get_min_count(&self) -> usize {
+        self.min_count
+    }
+
+    /// The most arguments this positional takes, where `last` says whether
+    /// it is the last positional.
+    pub (crate) fn get_max_count(&self, last: bool) -> usize {
+        self.max_count.unwrap_or(if last {usize::MAX} else {1})
+    }
+
+    pub (crate) fn is_required(&self) -> bool {
+        self.required
+    }
+
+    pub (crate) fn get_default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    pub (crate) fn is_ignored(&self) -> bool {
+        self.ignored
+    }
+
+    pub (crate) fn is_counted(&self) -> bool {
+        self.count.is_some()
+    }
+
+    /// Runs the action for a counted flag that appeared `n` times.
+    pub (crate) fn parse_count(&self, n: usize) -> Option<T> {
+        self.count.as_ref().map(|count| count(n))
     }
 
     pub (crate) fn takes_parameter(&self) -> bool {
         !self.name.is_empty()
     }
 
-    pub (crate) fn get_short(&self) -> Option<char> {
-        self.short
+    pub (crate) fn is_toggle(&self) -> bool {
+        self.plus.is_some()
+    }
+
+    pub (crate) fn takes_optional_parameter(&self) -> bool {
+        self.bare.is_some()
+    }
+
+    /// The main short name followed by any aliases.
+    pub (crate) fn get_shorts(&self) -> impl Iterator<Item=char> + '_ {
+        self.short.into_iter().chain(self.short_aliases.iter().cloned())
+    }
+
+    pub (crate) fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub (crate) fn get_description(&self) -> &str {
+        &self.descr
     }
 
     pub (crate) fn get_long(&self) -> Option<&str> {
         non_empty_string(&self.long)
     }
 
+    pub (crate) fn get_long_aliases(&self) -> &[String] {
+        &self.long_aliases
+    }
+
+    /// The main long name, if any, followed by any aliases.
+    pub (crate) fn get_longs(&self) -> impl Iterator<Item=&str> {
+        self.get_long().into_iter().chain(self.long_aliases.iter().map(String::as_str))
+    }
+
     pub (crate) fn positional_name(&self) -> &str {
-        static ARG: &'static str = "ARG";
+        static ARG: &str = "ARG";
 
         if self.name.is_empty() {
             ARG
@@ -177,8 +778,44 @@ impl<'a, T> Arg<'a, T> {
     /// `&self` – the formal `Arg` we are looking for
     ///
     /// `param` – the parameter supplied to the option, if any.
-    pub (crate) fn parse_argument<'b>(&self, param: &'b str) -> Result<T> {
-        (self.action)(param)
+    pub (crate) fn parse_argument(&self, param: &str) -> Result<T> {
+        (self.action)(param).map_err(|e| self.name_type(e, param))
+    }
+
+    /// Runs the action on a parameter that is not valid Unicode, which only
+    /// an argument created by [`os_param`](#method.os_param) accepts.
+    pub (crate) fn parse_os_argument(&self, raw: &OsStr) -> Result<T> {
+        match self.os_action {
+            Some(ref action) => action(raw).map_err(|e| self.name_type(e, &raw.to_string_lossy())),
+            None             => Err(Error::from_string(
+                &format!("invalid Unicode: {}", raw.to_string_lossy()))),
+        }
+    }
+
+    /// Names the type and the parameter in a parse failure, if the argument
+    /// has a type name.
+    fn name_type(&self, error: Error, param: &str) -> Error {
+        match self.type_name {
+            Some(ref type_name) => error.with_type_name(&self.name, type_name, param),
+            None                => error,
+        }
+    }
+
+    /// Runs the action for an optional-parameter option given without its
+    /// parameter.
+    pub (crate) fn parse_bare(&self) -> Result<T> {
+        match self.bare {
+            Some(ref bare) => bare(),
+            None           => self.parse_argument(""),
+        }
+    }
+
+    /// Runs the action for a toggle switched on.
+    pub (crate) fn parse_plus(&self) -> Result<T> {
+        match self.plus {
+            Some(ref plus) => plus(),
+            None           => self.parse_argument(""),
+        }
     }
 }
 
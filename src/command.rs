@@ -0,0 +1,22 @@
+use std::ffi::OsStr;
+use std::process::Command;
+
+/// Builds a `Command` that runs `program` with `args`, for forwarding the
+/// arguments a wrapper did not handle itself to a subprocess.
+///
+/// # Example
+///
+/// ```
+/// let rest = vec!["-l".to_owned(), "/tmp".to_owned()];
+/// let command = foropts::to_command("ls", rest);
+/// assert_eq!( vec!["-l", "/tmp"], command.get_args().collect::<Vec<_>>() );
+/// ```
+pub fn to_command<P, I>(program: P, args: I) -> Command
+    where P: AsRef<OsStr>,
+          I: IntoIterator,
+          I::Item: AsRef<OsStr>
+{
+    let mut command = Command::new(program);
+    command.args(args);
+    command
+}
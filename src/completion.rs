@@ -0,0 +1,78 @@
+use super::*;
+
+/// A shell-neutral description of everything that can be completed on a
+/// command line, for rendering completions for any shell or editor.
+///
+/// Created by [`Config::completion_model`](struct.Config.html#method.completion_model).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompletionModel {
+    /// The name of the command being completed.
+    pub bin_name:       String,
+    /// The options, in the order they were added to the `Config`.
+    pub options:        Vec<OptionCompletion>,
+    /// The positional arguments, in order.
+    pub positionals:    Vec<PositionalCompletion>,
+}
+
+/// How to complete an option.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OptionCompletion {
+    /// The short names, main name first.
+    pub shorts:         Vec<char>,
+    /// The long name, if any.
+    pub long:           Option<String>,
+    /// Alternative long names.
+    pub long_aliases:   Vec<String>,
+    /// The name of the parameter, if the option takes one.
+    pub value_name:     Option<String>,
+    /// Whether the parameter may be omitted.
+    pub value_optional: bool,
+    /// The values the parameter is restricted to, or empty if it is not.
+    pub possible_values: Vec<String>,
+    /// The description from the help message.
+    pub description:    String,
+}
+
+/// How to complete a positional argument.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionalCompletion {
+    /// The name of the argument, as shown in usage.
+    pub name:           String,
+    /// The description from the help message.
+    pub description:    String,
+}
+
+impl CompletionModel {
+    pub (crate) fn new<T>(config: &Config<T>) -> Self {
+        let options = (0 .. config.arg_count())
+            .map(|i| config.get_arg(i))
+            .filter(|arg| !arg.is_positional())
+            .map(|arg| OptionCompletion {
+                shorts:         arg.get_shorts().collect(),
+                long:           arg.get_long().map(ToOwned::to_owned),
+                long_aliases:   arg.get_long_aliases().to_vec(),
+                value_name:     if arg.takes_parameter() {
+                    Some(arg.get_name().to_owned())
+                } else {
+                    None
+                },
+                value_optional: arg.takes_optional_parameter(),
+                possible_values: arg.get_possible_values().to_vec(),
+                description:    arg.get_description().to_owned(),
+            })
+            .collect();
+
+        let positionals = config.get_positionals()
+            .map(|arg| PositionalCompletion {
+                name:           arg.positional_name().to_owned(),
+                description:    arg.get_description().to_owned(),
+            })
+            .collect();
+
+        CompletionModel {
+            bin_name:       config.get_bin_name().to_owned(),
+            options,
+            positionals,
+        }
+    }
+}
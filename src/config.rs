@@ -1,9 +1,35 @@
 use super::*;
+use util::{is_negative_number, split_first_str, styled, BOLD, RED};
+use iter::analyze_argument;
+use os::OsArgs;
+use stdin::StdinArgs;
 
-use std::collections::hash_map::{self, HashMap};
-use std::io;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::{env, fmt, io, result};
 use std::process::exit;
 
+/// The output stream for help; see [`Config::show_help`](struct.Config.html#method.show_help).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HelpStream {
+    /// Standard output.
+    Stdout,
+    /// Standard error.
+    Stderr,
+}
+
+/// A Unicode normalization form in which option names are matched; see
+/// [`Config::normalize_unicode`](struct.Config.html#method.normalize_unicode).
+#[cfg(feature = "unicode-normalization")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Form {
+    /// Canonical composition, NFC.
+    Nfc,
+    /// Compatibility composition, NFKC, which also matches names such as
+    /// `ﬁle` against `file`.
+    Nfkc,
+}
+
 /// The configuration for the argument parser.
 ///
 /// # Parameters
@@ -14,13 +40,48 @@ use std::process::exit;
 #[derive(Debug)]
 pub struct Config<'a, T> {
     name:       String,
+    bin_name:   Option<String>,
     version:    Option<String>,
     author:     Option<String>,
     about:      Option<String>,
     args:       Vec<Arg<'a, T>>,
     short_map:  HashMap<char, usize>,
     long_map:   HashMap<String, usize>,
-    positional: Option<Arg<'a, T>>,
+    positionals: Vec<Arg<'a, T>>,
+    resume:     Option<String>,
+    escape:     Option<char>,
+    short_fold: bool,
+    long_fold:  bool,
+    #[cfg(feature = "unicode-normalization")]
+    normal_form: Option<Form>,
+    stdin:      Option<StdinArgs<'a>>,
+    no_empty:   bool,
+    value_last: bool,
+    preprocess: Option<Preprocess<'a>>,
+    end_marker: String,
+    resync:     bool,
+    max_positionals: Option<usize>,
+    max_tokens: Option<usize>,
+    strict_values: bool,
+    plus_minus: bool,
+    auto_help:  Option<usize>,
+    auto_version: Option<usize>,
+    abbrev:     bool,
+    dash_long:  bool,
+    neg_numbers: bool,
+    options_first: bool,
+    wrap_width: usize,
+    color:      bool,
+    error_code: i32,
+}
+
+/// A function applied to the whole argument list before parsing.
+struct Preprocess<'a>(Box<dyn Fn(Vec<String>) -> Vec<String> + 'a>);
+
+impl<'a> fmt::Debug for Preprocess<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("…")
+    }
 }
 
 impl<'a, T> Config<'a, T> {
@@ -28,13 +89,39 @@ impl<'a, T> Config<'a, T> {
     pub fn new<S: Into<String>>(name: S) -> Self {
         Config {
             name:       name.into(),
+            bin_name:   None,
             version:    None,
             author:     None,
             about:      None,
             args:       Vec::new(),
             short_map:  HashMap::new(),
             long_map:   HashMap::new(),
-            positional: None,
+            positionals: Vec::new(),
+            resume:     None,
+            escape:     None,
+            short_fold: false,
+            long_fold:  false,
+            #[cfg(feature = "unicode-normalization")]
+            normal_form: None,
+            stdin:      None,
+            no_empty:   false,
+            value_last: false,
+            preprocess: None,
+            end_marker: "--".to_owned(),
+            resync:     false,
+            max_positionals: None,
+            max_tokens: None,
+            strict_values: false,
+            plus_minus: false,
+            auto_help:  None,
+            auto_version: None,
+            abbrev:     false,
+            dash_long:  false,
+            neg_numbers: false,
+            options_first: false,
+            wrap_width: 80,
+            color:      false,
+            error_code: 1,
         }
     }
 
@@ -44,6 +131,14 @@ impl<'a, T> Config<'a, T> {
         self
     }
 
+    /// Sets the name of the invoked binary, which is shown in the usage line.
+    /// It defaults to the program name, which is still used for version
+    /// information.
+    pub fn bin_name<S: Into<String>>(mut self, bin_name: S) -> Self {
+        self.bin_name = Some(bin_name.into());
+        self
+    }
+
     /// Sets the version string.
     pub fn version<S: Into<String>>(mut self, version: S) -> Self {
         self.version = Some(version.into());
@@ -62,46 +157,380 @@ impl<'a, T> Config<'a, T> {
         self
     }
 
+    /// Sets the width, in columns, to which option descriptions are wrapped
+    /// in usage information, which is 80 by default. Continuation lines are
+    /// aligned under the start of the description, and words are never
+    /// split, so a word too long for the space left runs past the width.
+    pub fn wrap_width(mut self, width: usize) -> Self {
+        self.wrap_width = width;
+        self
+    }
+
+    /// Sets whether usage information shows option names in bold and error
+    /// messages start with a red `Syntax error:`. Colors are off by default.
+    ///
+    /// The configuration does not check whether its output goes to a
+    /// terminal, so a program should enable colors only when it knows that
+    /// it does. Even when enabled, colors are not used for help and errors
+    /// printed to standard output or standard error if the `NO_COLOR`
+    /// environment variable is set and not empty.
+    pub fn colored(mut self, enabled: bool) -> Self {
+        self.color = enabled;
+        self
+    }
+
+    /// Sets the exit code used when the arguments do not parse, as by
+    /// [`exit_error`](#method.exit_error) and
+    /// [`parse_or_exit`](#method.parse_or_exit). The default is 1; tools
+    /// following `sysexits.h` use 64 (`EX_USAGE`).
+    pub fn usage_exit_code(mut self, code: i32) -> Self {
+        self.error_code = code;
+        self
+    }
+
+    /// Sets the argument that ends option parsing, which is `--` by default.
+    /// The marker must match a whole argument. When it is changed, `--` is an
+    /// ordinary positional argument.
+    pub fn end_of_options_marker<S: Into<String>>(mut self, marker: S) -> Self {
+        self.end_marker = marker.into();
+        self
+    }
+
+    /// Limits the number of positional arguments. Once `max` have been
+    /// parsed, each further positional argument is an error.
+    pub fn max_positionals(mut self, max: usize) -> Self {
+        self.max_positionals = Some(max);
+        self
+    }
+
+    /// Limits the number of arguments an iterator processes, counting each
+    /// option in a cluster separately. Arguments count whether or not they
+    /// yield a result, so option parameters, `--`, and
+    /// [`ignored`](struct.Arg.html#method.ignored) and
+    /// [`counted`](struct.Arg.html#method.counted) options all count. If more
+    /// arguments remain after `max`, the iterator yields the error
+    /// `argument limit exceeded` and stops. This bounds the work done on
+    /// untrusted input.
+    pub fn max_tokens(mut self, max: usize) -> Self {
+        self.max_tokens = Some(max);
+        self
+    }
+
+    /// Sets a marker that, when seen after option parsing has stopped (as it
+    /// does after `--`), resumes option parsing. The marker itself is
+    /// discarded.
+    pub fn resume_options_marker<S: Into<String>>(mut self, marker: S) -> Self {
+        self.resume = Some(marker.into());
+        self
+    }
+
+    /// Stops option parsing at the first positional argument, as POSIX
+    /// `getopt` does, so that all the arguments after it are positional even
+    /// if they start with `-`. By default, options may follow positional
+    /// arguments. A [resume marker](#method.resume_options_marker) still
+    /// resumes option parsing.
+    pub fn require_options_first(mut self) -> Self {
+        self.options_first = true;
+        self
+    }
+
+    /// Sets an escape character that, placed before a leading `-`, makes an
+    /// argument positional. The escape character is removed, so with
+    /// `escape_char('\\')`, the argument `\--` is the positional `--`.
+    pub fn escape_char(mut self, c: char) -> Self {
+        self.escape = Some(c);
+        self
+    }
+
+    /// Sets whether a marker argument, `-` by default, reads more arguments
+    /// from stdin. The arguments are split on whitespace and take the place of
+    /// the marker. Seeing the marker again in the same arguments is an error.
+    ///
+    /// Stdin is read once per configuration, the first time the marker is
+    /// seen, and kept: every iterator over the configuration, including a
+    /// [checkpoint](struct.Iter.html#method.checkpoint) and one created for a
+    /// later parse, splices in the same arguments.
+    ///
+    /// When this is disabled, `-` is an ordinary positional argument.
+    pub fn args_from_stdin(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.stdin = None;
+        } else if self.stdin.is_none() {
+            self.stdin = Some(StdinArgs::new());
+        }
+        self
+    }
+
+    /// Sets the marker argument that reads more arguments from stdin, and
+    /// enables [`args_from_stdin`](#method.args_from_stdin).
+    pub fn stdin_marker<S: Into<String>>(self, marker: S) -> Self {
+        let mut config = self.args_from_stdin(true);
+        config.stdin.as_mut().unwrap().set_marker(marker.into());
+        config
+    }
+
+    /// Reads the additional arguments from `reader` instead of stdin, and
+    /// enables [`args_from_stdin`](#method.args_from_stdin).
+    pub fn stdin_reader<R: io::Read + 'a>(self, reader: R) -> Self {
+        let mut config = self.args_from_stdin(true);
+        config.stdin.as_mut().unwrap().set_reader(Box::new(reader));
+        config
+    }
+
+    /// Sets whether empty arguments are rejected with an error. By default, an
+    /// empty argument is an ordinary positional argument.
+    pub fn reject_empty_args(mut self, enabled: bool) -> Self {
+        self.no_empty = enabled;
+        self
+    }
+
+    /// Sets whether a short option that takes a parameter must come last in a
+    /// cluster. When enabled, if the rest of the cluster after such an option
+    /// consists entirely of known short options, as `v` in `-ov`, it is
+    /// reported as ambiguous rather than taken as the parameter.
+    pub fn value_short_must_be_last(mut self, enabled: bool) -> Self {
+        self.value_last = enabled;
+        self
+    }
+
+    /// Sets whether an option that requires a parameter refuses to take the
+    /// following argument as its parameter when that argument is itself a
+    /// known option. When enabled, `--out --verbose` reports that `--out` is
+    /// missing its parameter rather than taking `--verbose` as the parameter.
+    /// A parameter attached to the option, as in `--out=--verbose`, is always
+    /// taken.
+    pub fn strict_value_consumption(mut self, enabled: bool) -> Self {
+        self.strict_values = enabled;
+        self
+    }
+
+    /// Sets whether arguments starting with `+` are options, so that a
+    /// [`toggle`](struct.Arg.html#method.toggle) is switched on by `+x` and
+    /// off by `-x`. Like `-`, `+` may start a cluster, as in `+xy`, but every
+    /// option in it must be a toggle. When this is disabled, an argument
+    /// starting with `+` is positional.
+    pub fn plus_minus_pairs(mut self, enabled: bool) -> Self {
+        self.plus_minus = enabled;
+        self
+    }
+
+    /// Allows a long option to be given by any prefix of its name that is not
+    /// also a prefix of another long option’s name, so that `--verb` means
+    /// `--verbose`. An exact match always wins, and an ambiguous prefix is an
+    /// error listing the candidates, as in `ambiguous (verbose, version)`.
+    pub fn allow_abbreviations(mut self) -> Self {
+        self.abbrev = true;
+        self
+    }
+
+    /// Allows long options to be given with a single dash, as in `-version`.
+    /// Short options take precedence: an argument like `-version` is a
+    /// cluster of short options if `-v` is a short option, and only
+    /// otherwise is it taken as the long option `--version`, if there is
+    /// one. A parameter may be attached with `=`, as in `-out=file`.
+    pub fn allow_single_dash_long(mut self) -> Self {
+        self.dash_long = true;
+        self
+    }
+
+    /// Treats an argument that is `-` followed by a number, such as `-3` or
+    /// `-3.5`, as positional rather than as short options. The whole
+    /// argument must parse as a number, so `-3abc` is still a cluster of
+    /// short options.
+    pub fn allow_negative_numbers(mut self) -> Self {
+        self.neg_numbers = true;
+        self
+    }
+
+    /// Sets a function that rewrites the whole argument list before parsing,
+    /// for example to expand aliases or response files.
+    ///
+    /// The function runs once, eagerly, when an iterator is created: all the
+    /// arguments are collected into a `Vec` and passed to it, and parsing
+    /// proceeds over the `Vec` it returns.
+    pub fn preprocess<F>(mut self, f: F) -> Self
+        where F: Fn(Vec<String>) -> Vec<String> + 'a
+    {
+        self.preprocess = Some(Preprocess(Box::new(f)));
+        self
+    }
+
+    /// Sets whether, after an error, parsing resumes at the next whole
+    /// argument. By default, when an option in a cluster of short options
+    /// like `-nab` is recognized but fails, as when its action returns an
+    /// error, parsing continues with the rest of the cluster, `-ab`. After an
+    /// unrecognized short option, the rest of its cluster is always skipped.
+    pub fn resync_on_error(mut self, enabled: bool) -> Self {
+        self.resync = enabled;
+        self
+    }
+
+    /// Sets whether short options match regardless of ASCII case, so that
+    /// `-A` and `-a` are the same option.
+    ///
+    /// # Panics
+    ///
+    /// Panics if already-added arguments have short names differing only in
+    /// case.
+    pub fn short_case_insensitive(mut self, enabled: bool) -> Self {
+        self.short_fold = enabled;
+        self.rebuild_short_map("short_case_insensitive");
+        self
+    }
+
+    /// Sets whether long options match regardless of ASCII case, so that
+    /// `--Verbose` and `--verbose` are the same option. Usage information
+    /// still shows the names as they were given.
+    ///
+    /// # Panics
+    ///
+    /// Panics if already-added arguments have long names differing only in
+    /// case.
+    pub fn case_insensitive_long(mut self, enabled: bool) -> Self {
+        self.long_fold = enabled;
+        self.rebuild_long_map("case_insensitive_long");
+        self
+    }
+
+    /// Sets the Unicode normalization form in which option names are
+    /// matched, so that a name typed with a decomposed `é`, as `e` followed
+    /// by a combining accent, matches one added with the composed `é`.
+    /// Both the names added and the names looked up are normalized. A short
+    /// name is normalized when that gives a single character. Usage
+    /// information still shows the names as they were given.
+    ///
+    /// Requires the `unicode-normalization` feature.
+    ///
+    /// # Panics
+    ///
+    /// Panics if already-added arguments have names that normalize to the
+    /// same name.
+    #[cfg(feature = "unicode-normalization")]
+    pub fn normalize_unicode(mut self, form: Form) -> Self {
+        self.normal_form = Some(form);
+        self.rebuild_short_map("normalize_unicode");
+        self.rebuild_long_map("normalize_unicode");
+        self
+    }
+
+    /// Rebuilds the map from short names after the way they are folded
+    /// changes, panicking in `method` if two names now collide.
+    fn rebuild_short_map(&mut self, method: &str) {
+        let mut short_map = HashMap::new();
+        for (index, arg) in self.args.iter().enumerate() {
+            for c in arg.get_shorts() {
+                if short_map.insert(self.fold_short(c), index).is_some() {
+                    panic!("foropts::Config::{}: repeated arg", method);
+                }
+            }
+        }
+        self.short_map = short_map;
+    }
+
+    /// Rebuilds the map from long names after the way they are folded
+    /// changes, panicking in `method` if two names now collide.
+    fn rebuild_long_map(&mut self, method: &str) {
+        let mut long_map = HashMap::new();
+        for (index, arg) in self.args.iter().enumerate() {
+            for s in arg.get_longs() {
+                if long_map.insert(self.fold_long(s), index).is_some() {
+                    panic!("foropts::Config::{}: repeated arg", method);
+                }
+            }
+        }
+        self.long_map = long_map;
+    }
+
+    /// Adds `-h` and `--help` options that, when an iterator reaches them,
+    /// print usage information and exit, as with
+    /// [`exit_usage`](#method.exit_usage). They yield no result, so the
+    /// result type needs no variant for them.
+    ///
+    /// This is opt-in, so a program that uses `-h` for something else can
+    /// simply not call it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `-h` or `--help` is already in use.
+    pub fn auto_help(mut self) -> Result<Self> {
+        let index = self.arg_count();
+        self.arg_safe(Arg::reserved().short('h').long("help")
+                          .description("Print help information"))?;
+        self.auto_help = Some(index);
+        Ok(self)
+    }
+
+    /// Adds `-V` and `--version` options that, when an iterator reaches them,
+    /// print version information and exit, as with
+    /// [`exit_version`](#method.exit_version). Like the options added by
+    /// [`auto_help`](#method.auto_help), they yield no result.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no [`version`](#method.version) has been set, or if `-V` or
+    /// `--version` is already in use.
+    pub fn auto_version(mut self) -> Result<Self> {
+        if self.version.is_none() {
+            return Err(Error::from_string("no version set").with_option("--version"));
+        }
+
+        let index = self.arg_count();
+        self.arg_safe(Arg::reserved().short('V').long("version")
+                          .description("Print version information"))?;
+        self.auto_version = Some(index);
+        Ok(self)
+    }
+
+    /// Adds an option described by a spec such as `-o, --output <FILE>`, or
+    /// `--verbose` for a flag, whose action receives `""`. Fails if the spec
+    /// is malformed or if the option cannot be added, as with
+    /// [`arg_safe`](#method.arg_safe).
+    pub fn arg_from_spec<F>(&mut self, spec: &str, action: F) -> Result<()>
+        where F: Fn(&str) -> Result<T> + 'a
+    {
+        self.arg_safe(Arg::from_spec(spec, action)?)
+    }
+
     /// Adds an argument to the list of arguments, returning `Result::Err` if the
     /// argument cannot be added.
+    ///
+    /// An argument with no short or long name is positional. Positionals
+    /// take the positional arguments in the order they were added, with the
+    /// last one taking all that remain, as in `clone REPO DIR...`.
     pub fn arg_safe(&mut self, arg: Arg<'a, T>) -> Result<()> {
-        use self::hash_map::Entry::*;
-
         if arg.is_positional() {
-            if self.positional.is_none () {
-                self.positional = Some(arg);
-                return Ok(());
-            } else {
-                return Err(Error::from_string("multiple positional arguments"))
-            }
+            self.positionals.push(arg);
+            return Ok(());
         }
 
         let index = self.args.len();
 
-        if let Some(c) = arg.get_short() {
-            match self.short_map.entry(c) {
-                Vacant(entry) => {
-                    entry.insert(index);
-                }
-                Occupied(_)   => {
-                    return Err(Error::from_string("repeated in config")
-                        .with_option(format!("-{}", c)));
-                }
+        let mut short_keys = Vec::new();
+        for c in arg.get_shorts() {
+            let key = self.fold_short(c);
+            if self.short_map.contains_key(&key) || short_keys.contains(&key) {
+                return Err(Error::from_string("repeated in config")
+                    .with_option(format!("-{}", c)));
             }
+            short_keys.push(key);
         }
 
-        if let Some(s) = arg.get_long() {
-            match self.long_map.entry(s.to_owned()) {
-                Vacant(entry) => {
-                    entry.insert(index);
-                }
-                Occupied(_)   => {
-                    return Err(Error::from_string("repeated in config")
-                        .with_option(format!("--{}", s)));
-                }
+        let long_keys: Vec<String> = arg.get_longs().map(|s| self.fold_long(s)).collect();
+        for (i, key) in long_keys.iter().enumerate() {
+            if self.long_map.contains_key(key) || long_keys[.. i].contains(key) {
+                let s = arg.get_longs().nth(i).unwrap_or(key);
+                return Err(Error::from_string("repeated in config")
+                    .with_option(format!("--{}", s)));
             }
         }
 
+        for key in short_keys {
+            self.short_map.insert(key, index);
+        }
+        for key in long_keys {
+            self.long_map.insert(key, index);
+        }
+
         self.args.push(arg);
 
         Ok(())
@@ -117,6 +546,19 @@ impl<'a, T> Config<'a, T> {
         self
     }
 
+    /// Adds a positional argument whose values are each parsed by
+    /// `str::parse` and then transformed by `wrapper`. A value that fails to
+    /// parse is reported as for [`Arg::parsed_param`](struct.Arg.html#method.parsed_param),
+    /// as in `invalid value for <N:u32>: '2x'`.
+    pub fn positional_parsed<A, S, F>(self, name: S, wrapper: F) -> Self
+        where S: Into<String>,
+              F: Fn(A) -> T + 'a,
+              A: FromStr,
+              A::Err: ToString
+    {
+        self.arg(Arg::parsed_param(name, wrapper))
+    }
+
     /// Adds arguments to the list of arguments.
     ///
     /// # Panics
@@ -129,25 +571,298 @@ impl<'a, T> Config<'a, T> {
         self
     }
 
+    /// Compares two configurations, ignoring the arguments’ actions, which
+    /// cannot be compared.
+    pub fn structurally_eq(&self, other: &Self) -> bool {
+        fn args_eq<'a, T>(a: &[Arg<'a, T>], b: &[Arg<'a, T>]) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.structurally_eq(y))
+        }
+
+        self.name == other.name
+            && self.bin_name == other.bin_name
+            && self.version == other.version
+            && self.author == other.author
+            && self.about == other.about
+            && args_eq(&self.args, &other.args)
+            && args_eq(&self.positionals, &other.positionals)
+            && self.resume == other.resume
+            && self.escape == other.escape
+            && self.short_fold == other.short_fold
+            && self.long_fold == other.long_fold
+            && self.same_normal_form(other)
+            && self.no_empty == other.no_empty
+            && self.value_last == other.value_last
+            && self.preprocess.is_some() == other.preprocess.is_some()
+            && self.end_marker == other.end_marker
+            && self.resync == other.resync
+            && self.max_positionals == other.max_positionals
+            && self.max_tokens == other.max_tokens
+            && self.strict_values == other.strict_values
+            && self.plus_minus == other.plus_minus
+            && self.auto_help == other.auto_help
+            && self.auto_version == other.auto_version
+            && self.abbrev == other.abbrev
+            && self.dash_long == other.dash_long
+            && self.neg_numbers == other.neg_numbers
+            && self.options_first == other.options_first
+            && self.wrap_width == other.wrap_width
+            && self.color == other.color
+            && self.error_code == other.error_code
+            && self.stdin.as_ref().map(StdinArgs::marker)
+                == other.stdin.as_ref().map(StdinArgs::marker)
+    }
+
     /// Given an iterator over the unparsed arguments, returns an iterator over the
     /// parsed arguments.
     pub fn iter<'b, I: IntoIterator<Item=String>>(&'b self, args: I) -> Iter<'b, 'a, I, T> {
         Iter::new(self, args)
     }
 
+    /// Describes how each argument would be interpreted, without running any
+    /// actions. This is a diagnostic: it reports options and their parameters,
+    /// positional arguments, markers and errors, classifying the arguments
+    /// and taking parameters just as [`iter`](#method.iter) does, in every
+    /// mode of the configuration.
+    ///
+    /// As no action runs, each is taken to succeed, and the following are
+    /// not modeled:
+    ///
+    ///  - errors from actions, including those for parameters that do not
+    ///    parse or are not among the
+    ///    [`possible_values`](struct.Arg.html#method.possible_values);
+    ///  - the options added by [`auto_help`](#method.auto_help) and
+    ///    [`auto_version`](#method.auto_version), which do not exit;
+    ///  - arguments read from stdin, as the marker is reported as
+    ///    [`FromStdin`](enum.Explanation.html#variant.FromStdin) and nothing
+    ///    is read;
+    ///  - the checks made once the arguments run out, for
+    ///    [`required`](struct.Arg.html#method.required) and
+    ///    [`requires`](struct.Arg.html#method.requires) options and the
+    ///    [`min_count`](struct.Arg.html#method.min_count) of positionals,
+    ///    along with defaults and the totals of
+    ///    [`counted`](struct.Arg.html#method.counted) flags.
+    pub fn explain<I: IntoIterator<Item=String>>(&self, args: I) -> Vec<Explanation> {
+        explain::explain(self, args)
+    }
+
+    /// Parses the arguments, without running any actions, into a normalized
+    /// command line: each option is written separately under its long name
+    /// if it has one, as `--name` or `--name=value`, and otherwise under its
+    /// main short name. A [`toggle`](struct.Arg.html#method.toggle) switched
+    /// off keeps its short form, `-x`, and each further value taken by a
+    /// [`values_until_flag`](struct.Arg.html#method.values_until_flag)
+    /// option is written as another occurrence of the option. The options
+    /// keep their order, followed by the end-of-options marker and the
+    /// positional arguments in their order.
+    /// The arguments are read as by [`explain`](#method.explain), and the
+    /// command line returned is checked to be read back the same way.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error that [`explain`](#method.explain) reports.
+    /// Also fails on the marker for reading arguments from stdin, and when no
+    /// rewriting parses the same way, such as when a positional argument
+    /// equals the [resume marker](#method.resume_options_marker) or a
+    /// parameter can be neither attached to its short option nor passed
+    /// separately.
+    pub fn canonicalize<I: IntoIterator<Item=String>>(&self, args: I) -> Result<Vec<String>> {
+        explain::canonicalize(self, args)
+    }
+
+    /// Like [`iter`](#method.iter), but over `OsString` arguments, which need
+    /// not be valid Unicode.
+    ///
+    /// Arguments are classified by their lossy Unicode form, but a positional
+    /// argument or option parameter that is not valid Unicode is passed
+    /// unchanged to an action created by
+    /// [`Arg::os_param`](struct.Arg.html#method.os_param); any other action
+    /// rejects it with an error. (On platforms other than Unix, a parameter
+    /// attached to its option, as in `--out=…`, is passed in its lossy
+    /// form.) Invalid Unicode in an option name is an unrecognized option. Arguments rewritten by
+    /// [`preprocess`](#method.preprocess) or read from stdin are taken in
+    /// their lossy form.
+    pub fn iter_os<'b, J>(&'b self, args: J) -> Iter<'b, 'a, OsArgs<J::IntoIter>, T>
+        where J: IntoIterator<Item=OsString>
+    {
+        let args = OsArgs::new(args.into_iter());
+        let slot = args.raw_slot();
+        Iter::new(self, args).with_raw_slot(slot)
+    }
+
+    /// Lists the options and positional arguments with the metadata needed
+    /// to complete them, independent of any particular shell.
+    pub fn completion_model(&self) -> CompletionModel {
+        CompletionModel::new(self)
+    }
+
+    /// Like [`iter`](#method.iter), but yields only the successfully parsed
+    /// results, recording errors to be retrieved with
+    /// [`LenientIter::errors`](struct.LenientIter.html#method.errors), so
+    /// that every error in the arguments can be reported at once.
+    pub fn iter_lenient<'b, I>(&'b self, args: I) -> LenientIter<'b, 'a, I, T>
+        where I: IntoIterator<Item=String>
+    {
+        LenientIter::new(self, args)
+    }
+
+    /// Like [`iter`](#method.iter), but pairs each result with its occurrence
+    /// index: 0 the first time an option appears, 1 the second time, and so
+    /// on. Positional arguments are counted together. Counted totals and
+    /// defaults, which come once the arguments run out, are paired with the
+    /// number of times their option appeared; see
+    /// [`IndexedIter`](struct.IndexedIter.html).
+    pub fn iter_indexed<'b, I>(&'b self, args: I) -> IndexedIter<'b, 'a, I, T>
+        where I: IntoIterator<Item=String>
+    {
+        IndexedIter::new(self, args)
+    }
+
+    /// Given an iterator over the unparsed arguments, returns a cursor for
+    /// parsing them manually.
+    pub fn cursor<'b, I: IntoIterator<Item=String>>(&'b self, args: I) -> Cursor<'b, 'a, I, T> {
+        Cursor::new(self, args)
+    }
+
+    /// Parses the arguments, pushing the results onto `out`, which is cleared
+    /// first if `clear` is true. This lets repeated parses reuse the same
+    /// `Vec`.
+    ///
+    /// On error, `out` holds the results parsed before the error.
+    pub fn parse_into<I>(&self, args: I, out: &mut Vec<T>, clear: bool) -> Result<()>
+        where I: IntoIterator<Item=String>
+    {
+        if clear {
+            out.clear();
+        }
+
+        for result in self.iter(args) {
+            out.push(result?);
+        }
+
+        Ok(())
+    }
+
+    /// Parses the arguments, folding each result into `init` with `f`, as
+    /// into a settings struct. Stops at the first error.
+    ///
+    /// # Parameters
+    ///
+    /// `init` – the initial state
+    ///
+    /// `f` – the function that updates the state with one result
+    ///
+    /// # Errors
+    ///
+    /// The first error parsing the arguments.
+    pub fn parse_fold<I, S, F>(&self, args: I, init: S, mut f: F) -> Result<S>
+        where I: IntoIterator<Item=String>,
+              F: FnMut(&mut S, T)
+    {
+        let mut state = init;
+
+        for result in self.iter(args) {
+            f(&mut state, result?);
+        }
+
+        Ok(state)
+    }
+
+    /// Parses the arguments, exiting with an error message and usage
+    /// information on stderr if they do not parse.
+    pub fn parse_or_exit<I>(&self, args: I) -> Vec<T>
+        where I: IntoIterator<Item=String>
+    {
+        let stdout = io::stdout();
+        let stderr = io::stderr();
+        let color  = self.uses_color();
+        self.parse_or_help(args, stdout.lock(), stderr.lock(), color)
+            .unwrap_or_else(|code| exit(code))
+    }
+
+    /// Parses the arguments, or on error returns a message ready to print,
+    /// naming the program and suggesting `--help`. Unlike
+    /// [`parse_or_exit`](#method.parse_or_exit), this leaves printing and
+    /// exiting to the caller.
+    pub fn parse_or_message<I>(&self, args: I) -> result::Result<Vec<T>, String>
+        where I: IntoIterator<Item=String>
+    {
+        self.iter(args).collect::<Result<_>>().map_err(|error| {
+            let bin_name = self.get_bin_name();
+            format!("{}: {}\nTry '{} --help' for more information.", bin_name, error, bin_name)
+        })
+    }
+
+    /// Parses the arguments, or on error writes help to the stream chosen by
+    /// [`show_help`](#method.show_help), in color if `color`, and returns the
+    /// exit code.
+    pub (crate) fn parse_or_help<I, O, E>(&self, args: I, out: O, mut err: E, color: bool)
+        -> result::Result<Vec<T>, i32>
+        where I: IntoIterator<Item=String>,
+              O: io::Write,
+              E: io::Write
+    {
+        self.iter(args).collect::<Result<_>>().map_err(|error| {
+            let _ = writeln!(err, "{} {}", styled("Syntax error:", RED, color), error);
+            let _ = self.write_help(HelpStream::Stderr, out, err, color);
+            self.error_exit_code()
+        })
+    }
+
     /// Exits with an error message and usage information printed on stderr,
-    /// with exit code 1.
+    /// with the exit code set by
+    /// [`usage_exit_code`](#method.usage_exit_code), 1 by default.
     pub fn exit_error(&self, error: &Error) -> ! {
-        eprintln!("Syntax error: {}", error);
-        self.write_usage(io::stderr()).unwrap();
-        exit(1);
+        let color = self.uses_color();
+        eprintln!("{} {}", styled("Syntax error:", RED, color), error);
+        self.show_help(HelpStream::Stderr, self.error_exit_code());
+    }
+
+    /// The exit code for arguments that do not parse.
+    pub (crate) fn error_exit_code(&self) -> i32 {
+        self.error_code
     }
 
     /// Prints usage information to stdout and exits with code 0.
     pub fn exit_usage(&self) -> ! {
+        self.show_help(HelpStream::Stdout, 0);
+    }
+
+    /// Prints usage information to the given stream and exits with the given
+    /// code.
+    ///
+    /// Help that was asked for goes to stdout with code 0, as in
+    /// [`exit_usage`](#method.exit_usage); help shown because of an error goes
+    /// to stderr with a non-zero code, as in
+    /// [`exit_error`](#method.exit_error).
+    pub fn show_help(&self, stream: HelpStream, code: i32) -> ! {
         let stdout = io::stdout();
-        self.write_usage(stdout.lock()).unwrap();
-        exit(0);
+        let stderr = io::stderr();
+        let color  = self.uses_color();
+        self.write_help(stream, stdout.lock(), stderr.lock(), color).unwrap();
+        exit(code);
+    }
+
+    /// Writes usage information to `out` or `err`, depending on `stream`, in
+    /// color if `color`.
+    pub (crate) fn write_help<O, E>(&self, stream: HelpStream, out: O, err: E, color: bool)
+        -> io::Result<()>
+        where O: io::Write,
+              E: io::Write
+    {
+        match stream {
+            HelpStream::Stdout => self.write_usage_styled(out, color),
+            HelpStream::Stderr => self.write_usage_styled(err, color),
+        }
+    }
+
+    /// Whether output to standard output or standard error should be in
+    /// color; see [`colored`](#method.colored).
+    fn uses_color(&self) -> bool {
+        let no_color = match env::var_os("NO_COLOR") {
+            Some(value) => !value.is_empty(),
+            None        => false,
+        };
+        self.color && !no_color
     }
 
     /// Prints version information to stdout and exits with code 0.
@@ -157,6 +872,27 @@ impl<'a, T> Config<'a, T> {
         exit(0);
     }
 
+    /// Returns the usage information that
+    /// [`write_usage`](#method.write_usage) writes.
+    pub fn usage_string(&self) -> String {
+        Self::write_to_string(|buf| self.write_usage(buf))
+    }
+
+    /// Returns the version information that
+    /// [`write_version`](#method.write_version) writes.
+    pub fn version_string(&self) -> String {
+        Self::write_to_string(|buf| self.write_version(buf))
+    }
+
+    /// Runs `write` on a buffer and returns what it wrote.
+    fn write_to_string<F>(write: F) -> String
+        where F: FnOnce(&mut Vec<u8>) -> io::Result<()>
+    {
+        let mut buf = Vec::new();
+        write(&mut buf).expect("writing to a Vec cannot fail");
+        String::from_utf8(buf).expect("usage information is UTF-8")
+    }
+
     /// Writes version information to the given `Write`.
     pub fn write_version<W: io::Write>(&self, mut out: W) -> io::Result<()> {
         write!(out, "{}", self.name)?;
@@ -166,18 +902,23 @@ impl<'a, T> Config<'a, T> {
         writeln!(out)
     }
 
-    fn write_usage_line<W: io::Write>(&self, mut out: W) -> io::Result<()> {
-        write!(out, "Usage: {} OPTION...", self.name)?;
+    fn write_usage_line<W: io::Write>(&self, mut out: W, color: bool) -> io::Result<()> {
+        write!(out, "{} {} OPTION...", styled("Usage:", BOLD, color), self.get_bin_name())?;
 
-        if let Some(ref arg) = self.positional {
-            writeln!(out, " [--] {}...", arg.positional_name())
-        } else {
+        if self.positionals.is_empty() {
             writeln!(out)
+        } else {
+            let names: Vec<_> = self.positionals.iter().map(Arg::positional_name).collect();
+            writeln!(out, " [{}] {}...", self.end_marker, names.join(" "))
         }
     }
 
     /// Writes usage information to the given `Write`.
-    pub fn write_usage<W: io::Write>(&self, mut out: W) -> io::Result<()> {
+    pub fn write_usage<W: io::Write>(&self, out: W) -> io::Result<()> {
+        self.write_usage_styled(out, self.color)
+    }
+
+    fn write_usage_styled<W: io::Write>(&self, mut out: W, color: bool) -> io::Result<()> {
         self.write_version(&mut out)?;
         if let Some(ref author) = self.author {
             writeln!(out, "{}", *author)?;
@@ -187,25 +928,313 @@ impl<'a, T> Config<'a, T> {
         }
         writeln!(out)?;
 
-        self.write_usage_line(&mut out)?;
+        self.write_usage_line(&mut out, color)?;
+
+        if !self.positionals.is_empty() {
+            writeln!(out, "\nARGS:")?;
+            for arg in &self.positionals {
+                arg.write_positional_usage(&mut out, self.wrap_width)?;
+            }
+        }
 
-        writeln!(out, "\nOPTIONS:")?;
+        let mut groups: Vec<Option<&str>> = Vec::new();
         for arg in &self.args {
-            arg.write_option_usage(&mut out)?;
+            if !groups.contains(&arg.get_group()) {
+                groups.push(arg.get_group());
+            }
+        }
+        groups.sort_by_key(Option::is_some);
+        if groups.is_empty() {
+            groups.push(None);
+        }
+
+        for group in groups {
+            writeln!(out, "\n{}:", group.unwrap_or("OPTIONS"))?;
+            for arg in self.args.iter().filter(|arg| arg.get_group() == group) {
+                arg.write_option_usage(&mut out, self.wrap_width, color)?;
+            }
         }
         Ok(())
     }
 
-    pub (crate) fn get_positional(&self) -> Option<&Arg<'a, T>> {
-        self.positional.as_ref()
+    pub (crate) fn get_end_marker(&self) -> &str {
+        &self.end_marker
+    }
+
+    /// Exits if the argument at `index` is one added by
+    /// [`auto_help`](#method.auto_help) or
+    /// [`auto_version`](#method.auto_version).
+    pub (crate) fn handle_reserved(&self, index: usize) {
+        if self.auto_help == Some(index) {
+            self.exit_usage();
+        }
+        if self.auto_version == Some(index) {
+            self.exit_version();
+        }
+    }
+
+    /// The name shown in the usage line.
+    pub (crate) fn get_bin_name(&self) -> &str {
+        self.bin_name.as_ref().unwrap_or(&self.name)
+    }
+
+    /// The formal positional arguments, in order.
+    pub (crate) fn get_positionals(&self) -> impl Iterator<Item=&Arg<'a, T>> {
+        self.positionals.iter()
+    }
+
+    pub (crate) fn get_max_tokens(&self) -> Option<usize> {
+        self.max_tokens
+    }
+
+    pub (crate) fn get_max_positionals(&self) -> Option<usize> {
+        self.max_positionals
     }
 
-    pub (crate) fn get_short(&self, c: char) -> Option<&Arg<'a, T>> {
-        self.short_map.get(&c).map(|i| &self.args[*i])
+    /// Gets the formal argument for the positional argument at `index`. Each
+    /// positional takes up to its maximum count in turn; this is `None` if
+    /// they are all full.
+    pub (crate) fn get_positional(&self, index: usize) -> Option<&Arg<'a, T>> {
+        let mut end: usize = 0;
+        for (arg, max) in self.positional_max_counts() {
+            end = end.saturating_add(max);
+            if index < end {
+                return Some(arg);
+            }
+        }
+        None
     }
 
-    pub (crate) fn get_long(&self, s: &str) -> Option<&Arg<'a, T>> {
-        self.long_map.get(s).map(|i| &self.args[*i])
+    /// The most positional arguments that the positionals take in all.
+    pub (crate) fn positional_capacity(&self) -> usize {
+        self.positional_max_counts().fold(0, |total: usize, (_, max)| total.saturating_add(max))
+    }
+
+    /// The positionals, each with the most arguments it takes.
+    fn positional_max_counts(&self) -> impl Iterator<Item=(&Arg<'a, T>, usize)> {
+        let last = self.positionals.len();
+        self.positionals.iter().enumerate()
+            .map(move |(i, arg)| (arg, arg.get_max_count(i + 1 == last)))
+    }
+
+    /// The first positional that takes fewer than its minimum count when
+    /// `count` positional arguments are given, if any.
+    pub (crate) fn short_positional(&self, count: usize) -> Option<&Arg<'a, T>> {
+        let mut left = count;
+        for (arg, max) in self.positional_max_counts() {
+            let taken = left.min(max);
+            if taken < arg.get_min_count() {
+                return Some(arg);
+            }
+            left -= taken;
+        }
+        None
+    }
+
+    pub (crate) fn requires_options_first(&self) -> bool {
+        self.options_first
+    }
+
+    pub (crate) fn is_resume_marker(&self, arg: &str) -> bool {
+        self.resume.as_deref() == Some(arg)
+    }
+
+    /// Classifies an argument, using the configured end-of-options marker.
+    pub (crate) fn classify<'s>(&self, arg: &'s str) -> ArgState<'s> {
+        if self.neg_numbers && is_negative_number(arg) {
+            return ArgState::Positional(arg);
+        }
+
+        match self.classify_cluster_rest(arg) {
+            ArgState::ShortOption(c, _) if self.dash_long && self.short_index(c).is_none() =>
+                self.classify_single_dash_long(arg),
+            state => state,
+        }
+    }
+
+    /// Classifies the remainder of a cluster of short options, which is
+    /// never taken as a single-dash long option.
+    pub (crate) fn classify_cluster_rest<'s>(&self, arg: &'s str) -> ArgState<'s> {
+        if arg == self.end_marker {
+            return ArgState::EndOfOptions;
+        }
+
+        if self.plus_minus {
+            if let Some(('+', rest)) = split_first_str(arg) {
+                if let Some((c, rest)) = split_first_str(rest) {
+                    return ArgState::PlusOption(c, rest);
+                }
+            }
+        }
+
+        match analyze_argument(arg) {
+            ArgState::EndOfOptions => ArgState::Positional(arg),
+            state                  => state,
+        }
+    }
+
+    /// Classifies `arg`, which starts with an unknown short option, as a long
+    /// option if its name is one.
+    fn classify_single_dash_long<'s>(&self, arg: &'s str) -> ArgState<'s> {
+        let rest = &arg[1 ..];
+        let (name, param) = match rest.find('=') {
+            Some(ix) => (&rest[.. ix], Some(&rest[ix + 1 ..])),
+            None     => (rest, None),
+        };
+
+        match self.long_index(name) {
+            Some(_) => ArgState::LongOption(name, param),
+            None    => analyze_argument(arg),
+        }
+    }
+
+    /// Applies the preprocessing function, if any, to the arguments.
+    pub (crate) fn preprocess_args<I>(&self, args: &mut I) -> Option<Vec<String>>
+        where I: Iterator<Item=String>
+    {
+        self.preprocess.as_ref().map(|f| (f.0)(args.collect()))
+    }
+
+    /// Whether `param`, attached to a short option that takes a parameter,
+    /// should be rejected as ambiguous.
+    pub (crate) fn is_ambiguous_cluster(&self, param: &str) -> bool {
+        self.value_last && param.chars().all(|c| self.short_index(c).is_some())
+    }
+
+    /// Whether `arg`, following an option that requires a parameter, should
+    /// be left alone rather than taken as the parameter.
+    pub (crate) fn refuses_as_param(&self, arg: &str) -> bool {
+        self.strict_values && match self.classify(arg) {
+            ArgState::ShortOption(c, _)    => self.short_index(c).is_some(),
+            ArgState::PlusOption(c, _)     => self.short_index(c).is_some(),
+            ArgState::LongOption(s, _)     => self.long_index(s).is_some(),
+            _                              => false,
+        }
+    }
+
+    pub (crate) fn resyncs_on_error(&self) -> bool {
+        self.resync
+    }
+
+    pub (crate) fn rejects_empty_args(&self) -> bool {
+        self.no_empty
+    }
+
+    /// Whether `arg` is the marker for reading arguments from stdin.
+    pub (crate) fn is_stdin_marker(&self, arg: &str) -> bool {
+        match self.stdin {
+            Some(ref stdin) => stdin.marker() == arg,
+            None            => false,
+        }
+    }
+
+    /// If `arg` is the marker for reading arguments from stdin, reads them, or
+    /// returns them if they were read already.
+    pub (crate) fn read_stdin_args(&self, arg: &str) -> Option<Result<Vec<String>>> {
+        let stdin = self.stdin.as_ref()?;
+        if stdin.marker() == arg {Some(stdin.read())} else {None}
+    }
+
+    /// If `arg` is escaped, returns it with the escape character removed.
+    pub (crate) fn unescape<'b>(&self, arg: &'b str) -> Option<&'b str> {
+        let rest = arg.strip_prefix(self.escape?)?;
+        if rest.starts_with('-') {Some(rest)} else {None}
+    }
+
+    pub (crate) fn arg_count(&self) -> usize {
+        self.args.len()
+    }
+
+    pub (crate) fn get_arg(&self, index: usize) -> &Arg<'a, T> {
+        &self.args[index]
+    }
+
+    pub (crate) fn short_index(&self, c: char) -> Option<usize> {
+        self.short_map.get(&self.fold_short(c)).cloned()
+    }
+
+    fn fold_short(&self, c: char) -> char {
+        let mut buf = [0; 4];
+        let c = match self.normalize(c.encode_utf8(&mut buf)) {
+            Some(ref s) if s.chars().count() == 1 => s.chars().next().unwrap_or(c),
+            _                                     => c,
+        };
+        if self.short_fold {c.to_ascii_lowercase()} else {c}
+    }
+
+    fn fold_long(&self, s: &str) -> String {
+        let s = self.normalize(s).unwrap_or_else(|| s.to_owned());
+        if self.long_fold {s.to_ascii_lowercase()} else {s}
+    }
+
+    /// `s` in the form set by [`normalize_unicode`](#method.normalize_unicode),
+    /// if one is set.
+    #[cfg(feature = "unicode-normalization")]
+    fn normalize(&self, s: &str) -> Option<String> {
+        use unicode_normalization::UnicodeNormalization;
+
+        match self.normal_form? {
+            Form::Nfc  => Some(s.nfc().collect()),
+            Form::Nfkc => Some(s.nfkc().collect()),
+        }
+    }
+
+    #[cfg(not(feature = "unicode-normalization"))]
+    fn normalize(&self, _s: &str) -> Option<String> {
+        None
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    fn same_normal_form(&self, other: &Self) -> bool {
+        self.normal_form == other.normal_form
+    }
+
+    #[cfg(not(feature = "unicode-normalization"))]
+    fn same_normal_form(&self, _other: &Self) -> bool {
+        true
+    }
+
+    pub (crate) fn long_index(&self, s: &str) -> Option<usize> {
+        self.lookup_long(s).ok()
+    }
+
+    /// Looks up a long option by name or, if enabled, by abbreviation.
+    pub (crate) fn lookup_long(&self, s: &str) -> Result<usize> {
+        let key = self.fold_long(s);
+        if let Some(&index) = self.long_map.get(&key) {
+            return Ok(index);
+        }
+
+        let unknown = || Error::unknown_flag(format!("--{}", s));
+        if !self.abbrev {
+            return Err(unknown());
+        }
+
+        let mut matches: Vec<(&String, usize)> = self.long_map.iter()
+            .filter(|&(name, _)| name.starts_with(&key))
+            .map(|(name, &index)| (name, index))
+            .collect();
+        matches.sort();
+
+        match matches.first() {
+            None                                                    => Err(unknown()),
+            Some(&(_, index)) if matches.iter().all(|m| m.1 == index) => Ok(index),
+            Some(_)                                                 =>
+                Err(Error::ambiguous(matches.into_iter().map(|(name, _)| name.as_str()))
+                    .with_option(format!("--{}", s))),
+        }
     }
 }
 
+impl<'a, T> Extend<Arg<'a, T>> for Config<'a, T> {
+    /// Adds arguments to the list of arguments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an argument cannot be added.
+    fn extend<I: IntoIterator<Item=Arg<'a, T>>>(&mut self, args: I) {
+        for arg in args {
+            self.arg_safe(arg).expect("foropts::Config::extend: repeated arg");
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use super::*;
+
+use std::fmt;
+
+/// A cursor for driving the parser manually, one argument at a time.
+///
+/// Created by [`Config::cursor`](struct.Config.html#method.cursor).
+///
+/// # Parameters
+///
+/// `<'a>` – the lifetime of app’s [`Config`](struct.Config.html)
+///
+/// `<'b>` - the lifetime of the argument processing actions (closures) in the `Config`
+///
+/// `<I>`  – the underlying `String` iterator from which we are getting the unprocessed arguments
+///
+/// `<T>`  – the type into which each argument is parsed
+pub struct Cursor<'a, 'b: 'a, I, T: 'a>
+    where I: IntoIterator<Item=String>
+{
+    iter:       Iter<'a, 'b, I, T>,
+    current:    Option<String>,
+}
+
+impl<'a, 'b, I, T> fmt::Debug for Cursor<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>,
+          Iter<'a, 'b, I, T>: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cursor")
+            .field("iter",      &self.iter)
+            .field("current",   &self.current)
+            .finish()
+    }
+}
+
+impl<'a, 'b, I, T> Cursor<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>
+{
+    pub (crate) fn new(config: &'a Config<'b, T>, args: I) -> Self {
+        Cursor {
+            iter:       Iter::new(config, args),
+            current:    None,
+        }
+    }
+
+    /// Takes the next unprocessed argument. After
+    /// [`parse_known`](#method.parse_known) parses the first option in a
+    /// cluster like `-ab`, this returns the rest of the cluster, `-b`.
+    pub fn next_raw(&mut self) -> Option<&str> {
+        self.current = self.iter.next_raw();
+        self.current.as_deref()
+    }
+
+    /// Classifies an argument syntactically, without looking up its options.
+    /// If the argument last taken by [`next_raw`](#method.next_raw) was the
+    /// rest of a cluster, `arg` is classified as that rest, which is never a
+    /// single-dash long option; see
+    /// [`Config::allow_single_dash_long`](struct.Config.html#method.allow_single_dash_long).
+    pub fn classify<'c>(&self, arg: &'c str) -> ArgState<'c> {
+        self.iter.classify(arg)
+    }
+
+    /// Parses `arg` as a single option or positional argument. If the option
+    /// takes a parameter that is not attached to `arg`, the parameter is taken
+    /// from the unprocessed arguments. As with an iterator, the options
+    /// added by [`Config::auto_help`](struct.Config.html#method.auto_help)
+    /// and [`Config::auto_version`](struct.Config.html#method.auto_version)
+    /// print their information and exit. `arg` is classified as by
+    /// [`classify`](#method.classify).
+    pub fn parse_known(&mut self, arg: &str) -> Result<T> {
+        let config = self.iter.config();
+        let state  = self.iter.classify(arg);
+        let (index, result) = self.iter.parse_classified(arg, state);
+        if let Some(index) = index {
+            config.handle_reserved(index);
+        }
+        result
+    }
+}
@@ -8,22 +8,115 @@ pub type Result<T> = result::Result<T, Error>;
 pub struct Error {
     option:     String,
     message:    String,
+    candidates: Option<Vec<String>>,
+    kind:       ErrorKind,
+}
+
+/// The kind of an argument parsing [`Error`](struct.Error.html).
+///
+/// More kinds may be added, so a `match` on a kind needs a wildcard arm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An option that is not in the configuration.
+    UnrecognizedOption,
+    /// An option that requires a parameter was not given one.
+    MissingParameter,
+    /// An option that takes no parameter was given one.
+    UnexpectedParameter,
+    /// A parameter could not be parsed to the expected type.
+    ParseFailure,
+    /// A positional argument where none, or no more, are accepted.
+    PositionalNotAccepted,
+    /// Any other error, including those returned by actions.
+    Other,
 }
 
 impl Error {
     /// Creates an argument error from any type that can be stringified.
     pub fn from_string<S: ToString + ?Sized>(e: &S) -> Self {
         Error {
-            option:    String::new(),
-            message:   e.to_string(),
+            option:     String::new(),
+            message:    e.to_string(),
+            candidates: None,
+            kind:       ErrorKind::Other,
         }
     }
 
+    /// Creates an error for an option that is not in the configuration.
+    pub fn unknown_flag<S: Into<String>>(option: S) -> Self {
+        Error::from_string("unrecognized").with_option(option)
+            .with_kind(ErrorKind::UnrecognizedOption)
+    }
+
+    /// Creates an error for an option that requires a parameter but was not
+    /// given one.
+    pub fn missing_param<S: Into<String>>(option: S) -> Self {
+        Error::from_string("expected option parameter").with_option(option)
+            .with_kind(ErrorKind::MissingParameter)
+    }
+
+    /// Creates an error for an option that takes no parameter but was given
+    /// one.
+    pub fn unexpected_param<S: Into<String>>(option: S, value: &str) -> Self {
+        Error::from_string(&format!("unexpected option parameter '{}'", value))
+            .with_option(option)
+            .with_kind(ErrorKind::UnexpectedParameter)
+    }
+
+    /// Creates an error for an abbreviated option that matches more than one
+    /// option, given the names of the candidate options. This is the error
+    /// reported for an ambiguous prefix when
+    /// [`Config::allow_abbreviations`](struct.Config.html#method.allow_abbreviations)
+    /// is enabled. Its kind is [`Other`](enum.ErrorKind.html#variant.Other);
+    /// [`ambiguous_candidates`](#method.ambiguous_candidates) tells it apart.
+    pub fn ambiguous<I>(candidates: I) -> Self
+        where I: IntoIterator,
+              I::Item: Into<String>
+    {
+        let candidates: Vec<String> = candidates.into_iter().map(Into::into).collect();
+        let mut error = Error::from_string(&format!("ambiguous ({})", candidates.join(", ")));
+        error.candidates = Some(candidates);
+        error
+    }
+
     /// Sets the particular option that triggered the error.
     pub fn with_option<S: Into<String>>(mut self, option: S) -> Self {
         self.option = option.into();
         self
     }
+
+    /// Sets the kind of the error, which does not affect its message.
+    pub fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Rewrites a parse failure as
+    /// `invalid value for <NAME:TYPE>: 'value' (cause)`, leaving other errors
+    /// alone.
+    pub (crate) fn with_type_name(mut self, name: &str, type_name: &str, value: &str) -> Self {
+        if self.kind == ErrorKind::ParseFailure {
+            let head = format!("invalid value for <{}:{}>: '{}'", name, type_name, value);
+            self.message = if self.message.is_empty() {
+                head
+            } else {
+                format!("{} ({})", head, self.message)
+            };
+        }
+        self
+    }
+
+    /// The kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The names of the options that an ambiguous abbreviation could refer
+    /// to, or `None` if this is not an ambiguity error.
+    pub fn ambiguous_candidates(&self) -> Option<&[String]> {
+        self.candidates.as_deref()
+    }
 }
 
 impl ::std::error::Error for Error {
@@ -0,0 +1,191 @@
+use super::*;
+
+use iter::{limit_exceeded, Given, Param, Step};
+
+/// How [`Config::explain`](struct.Config.html#method.explain) interprets an
+/// argument.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Explanation {
+    /// A short option, with its parameter if it has one.
+    Short(char, Option<String>),
+    /// A toggle switched on with `+`.
+    Plus(char),
+    /// A long option, with its parameter if it has one.
+    Long(String, Option<String>),
+    /// A positional argument.
+    Positional(String),
+    /// The end-of-options marker `--`.
+    EndOfOptions,
+    /// The marker set by
+    /// [`Config::resume_options_marker`](struct.Config.html#method.resume_options_marker).
+    ResumeOptions,
+    /// The marker set by
+    /// [`Config::args_from_stdin`](struct.Config.html#method.args_from_stdin),
+    /// whose arguments are not read.
+    FromStdin(String),
+    /// An argument that does not parse.
+    Error(Error),
+}
+
+pub (crate) fn explain<T, I>(config: &Config<T>, args: I) -> Vec<Explanation>
+    where I: IntoIterator<Item=String>
+{
+    let mut iter   = Iter::new(config, args);
+    let mut result = Vec::new();
+    // How the option taking further values was given.
+    let mut last   = None;
+
+    while let Some(step) = iter.next_step() {
+        let explanation = match step {
+            Step::Option(index, given, param, arg) => match iter.accept(index, &arg) {
+                Ok(_)  => {
+                    let explanation = explain_option(&given, param);
+                    last = Some(given);
+                    explanation
+                }
+                Err(e) => Explanation::Error(e),
+            },
+            Step::Value(_, value, _)               => match last {
+                Some(ref given) => explain_option(given, Param::Value(value, None)),
+                None            => unreachable!("explain: value without an option"),
+            },
+            Step::Positional(_, actual, _)         => Explanation::Positional(actual),
+            Step::EndOfOptions                     => Explanation::EndOfOptions,
+            Step::ResumeOptions                    => Explanation::ResumeOptions,
+            Step::FromStdin(marker)                => Explanation::FromStdin(marker),
+            Step::Error(e)                         => Explanation::Error(e),
+        };
+
+        if let Explanation::Error(_) = explanation {
+            iter.resync();
+        }
+
+        if iter.over_budget() {
+            result.push(Explanation::Error(limit_exceeded()));
+            return result;
+        }
+
+        result.push(explanation);
+    }
+
+    if iter.over_budget() {
+        result.push(Explanation::Error(limit_exceeded()));
+    }
+
+    result
+}
+
+/// Explains an option given as `given` with parameter `param`.
+fn explain_option(given: &Given, param: Param) -> Explanation {
+    let param = match param {
+        Param::Value(param, _) => Some(param),
+        Param::Flag
+            | Param::Bare      => None,
+    };
+
+    match *given {
+        Given::Short(c)     => Explanation::Short(c, param),
+        Given::Plus(c)      => Explanation::Plus(c),
+        Given::Long(ref s)  => Explanation::Long(s.clone(), param),
+    }
+}
+
+pub (crate) fn canonicalize<T, I>(config: &Config<T>, args: I) -> Result<Vec<String>>
+    where I: IntoIterator<Item=String>
+{
+    let explanations = explain(config, args);
+    let original     = meaning(config, &explanations)?;
+
+    let mut options     = Vec::new();
+    let mut positionals = Vec::new();
+
+    for explanation in explanations {
+        let (index, param, short) = match explanation {
+            Explanation::Short(c, param) => (config.short_index(c), param, true),
+            Explanation::Long(s, param)  => (config.long_index(&s), param, false),
+            Explanation::Plus(c)         => {
+                options.push(format!("+{}", c));
+                continue;
+            }
+            Explanation::Positional(s)   => {
+                positionals.push(s);
+                continue;
+            }
+            _                            => continue,
+        };
+
+        // A toggle’s long name switches it on, so `-x` must keep its short form.
+        let formal = config.get_arg(index.expect("canonicalize: explained option is known"));
+        let long   = if short && formal.is_toggle() {None} else {formal.get_long()};
+        match (long, formal.get_shorts().next(), param) {
+            (Some(long), _, None)        => options.push(format!("--{}", long)),
+            (Some(long), _, Some(param)) => options.push(format!("--{}={}", long, param)),
+            (None, Some(c), None)        => options.push(format!("-{}", c)),
+            (None, Some(c), Some(param)) => {
+                let attachable = !param.is_empty() && !config.is_ambiguous_cluster(&param);
+                if !formal.takes_optional_parameter() && !config.refuses_as_param(&param) {
+                    options.push(format!("-{}", c));
+                    options.push(param);
+                } else if attachable {
+                    options.push(format!("-{}{}", c, param));
+                } else {
+                    return Err(no_canonical_form());
+                }
+            }
+            (None, None, _)              => unreachable!("canonicalize: option without a name"),
+        }
+    }
+
+    if !positionals.is_empty() {
+        options.push(config.get_end_marker().to_owned());
+        options.extend(positionals);
+    }
+
+    // Rewriting can still change how an argument is classified, as when a
+    // positional becomes the resume marker once it follows `--`.
+    if meaning(config, &explain(config, options.clone())).ok() != Some(original) {
+        return Err(no_canonical_form());
+    }
+
+    Ok(options)
+}
+
+/// What a command line means, regardless of how it is written: each
+/// option’s index, whether it is a toggle switched on, and its parameter, in
+/// order, along with the positional arguments in order.
+type Meaning = (Vec<(usize, bool, Option<String>)>, Vec<String>);
+
+/// The meaning of the explained arguments. Fails on the first error, or on
+/// the marker for reading from stdin.
+fn meaning<T>(config: &Config<T>, explanations: &[Explanation]) -> Result<Meaning> {
+    let mut options     = Vec::new();
+    let mut positionals = Vec::new();
+
+    for explanation in explanations {
+        let (index, short, param) = match *explanation {
+            Explanation::Short(c, ref param) => (config.short_index(c), true, param.clone()),
+            Explanation::Plus(c)             => (config.short_index(c), false, None),
+            Explanation::Long(ref s, ref param) => (config.long_index(s), false, param.clone()),
+            Explanation::Positional(ref s)   => {
+                positionals.push(s.clone());
+                continue;
+            }
+            Explanation::EndOfOptions
+                | Explanation::ResumeOptions => continue,
+            Explanation::FromStdin(ref marker) => return Err(
+                Error::from_string("cannot canonicalize arguments read from stdin")
+                    .with_option(marker.as_str())),
+            Explanation::Error(ref e)        => return Err(e.clone()),
+        };
+
+        let index = index.expect("canonicalize: explained option is known");
+        options.push((index, !short && config.get_arg(index).is_toggle(), param));
+    }
+
+    Ok((options, positionals))
+}
+
+fn no_canonical_form() -> Error {
+    Error::from_string("arguments have no canonical form that parses the same way")
+}
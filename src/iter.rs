@@ -1,6 +1,12 @@
 use util::*;
 use super::*;
 
+use os::*;
+
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fmt;
+
 /// The iterator over the processed arguments.
 ///
 /// # Parameters
@@ -19,16 +25,319 @@ pub struct Iter<'a, 'b: 'a, I, T: 'a>
     config:     &'a Config<'b, T>,
     args:       I::IntoIter,
     push_back:  Option<String>,
+    push_back_raw: Option<OsString>,
+    in_cluster: bool,
+    pending:    VecDeque<String>,
+    raw_slot:   Option<RawSlot>,
+    current_raw: Option<OsString>,
+    positional: bool,
+    positional_count: usize,
+    counts:     Vec<usize>,
+    occurrence: usize,
+    warnings:   Vec<String>,
+    consumed:   usize,
+    stdin_read: bool,
+    exhausted:  bool,
+    collecting: Option<usize>,
+    finishing:  usize,
+    replay:     VecDeque<RawArg>,
+    recording:  Option<Vec<RawArg>>,
+    peeked:     Option<Peeked<T>>,
+}
+
+/// An argument along with its raw form, if that is not valid Unicode.
+type RawArg = (String, Option<OsString>);
+
+/// A result parsed ahead by [`Iter::peek`](struct.Iter.html#method.peek).
+///
+/// A clone of the iterator cannot copy the result, so instead it parses it
+/// again, starting from the state before the peek and taking first the
+/// arguments that the peek took.
+#[derive(Debug)]
+struct Peeked<T> {
+    item:       Option<Result<T>>,
+    before:     Snapshot,
+    taken:      Vec<RawArg>,
+}
+
+/// The parsing state of an [`Iter`](struct.Iter.html), apart from the
+/// underlying iterator and any peeked result.
+#[derive(Clone, Debug)]
+struct Snapshot {
+    push_back:  Option<String>,
+    push_back_raw: Option<OsString>,
+    in_cluster: bool,
+    pending:    VecDeque<String>,
+    current_raw: Option<OsString>,
     positional: bool,
+    positional_count: usize,
+    counts:     Vec<usize>,
+    occurrence: usize,
+    warnings:   Vec<String>,
+    consumed:   usize,
+    stdin_read: bool,
+    exhausted:  bool,
+    collecting: Option<usize>,
+    finishing:  usize,
+}
+
+/// An argument taken by [`Iter::next_step`](struct.Iter.html#method.next_step)
+/// and matched against the configuration, before any action runs.
+#[derive(Debug)]
+pub (crate) enum Step {
+    /// The option at the index, as given, with its parameter and the
+    /// argument it was taken from.
+    Option(usize, Given, Param, String),
+    /// A further value of the
+    /// [`values_until_flag`](struct.Arg.html#method.values_until_flag)
+    /// option at the index.
+    Value(usize, String, Option<OsString>),
+    /// The positional argument with the index among all positional
+    /// arguments.
+    Positional(usize, String, Option<OsString>),
+    /// The end-of-options marker.
+    EndOfOptions,
+    /// The marker set by
+    /// [`Config::resume_options_marker`](struct.Config.html#method.resume_options_marker).
+    ResumeOptions,
+    /// The marker set by
+    /// [`Config::args_from_stdin`](struct.Config.html#method.args_from_stdin),
+    /// whose arguments are not read yet.
+    FromStdin(String),
+    /// An argument that does not parse.
+    Error(Error),
+}
+
+/// How an option was given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub (crate) enum Given {
+    /// As a short option, `-c`.
+    Short(char),
+    /// As a toggle switched on, `+c`.
+    Plus(char),
+    /// As a long option, under the name as written.
+    Long(String),
+}
+
+/// The parameter taken by an option.
+#[derive(Debug)]
+pub (crate) enum Param {
+    /// None, as the option takes no parameter.
+    Flag,
+    /// None, as the option's parameter is optional and was left out.
+    Bare,
+    /// The parameter, with its raw form if that is not valid Unicode.
+    Value(String, Option<OsString>),
+}
+
+/// The iterator over the processed arguments with their occurrence indices.
+///
+/// Created by [`Config::iter_indexed`](struct.Config.html#method.iter_indexed).
+/// Each result is paired with the number of times the same option, or any
+/// positional argument, appeared before it. A result yielded once the
+/// arguments run out is paired with the number of times its option appeared
+/// in all: the total for a [`counted`](struct.Arg.html#method.counted) flag,
+/// and 0 for a [default](struct.Arg.html#method.default_value).
+pub struct IndexedIter<'a, 'b: 'a, I, T: 'a>
+    where I: IntoIterator<Item=String>
+{
+    iter:       Iter<'a, 'b, I, T>,
+}
+
+impl<'a, 'b, I, T> fmt::Debug for IndexedIter<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>,
+          Iter<'a, 'b, I, T>: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IndexedIter")
+            .field("iter",      &self.iter)
+            .finish()
+    }
+}
+
+impl<'a, 'b, I, T> Iterator for IndexedIter<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>
+{
+    type Item = Result<(usize, T)>;
+
+    fn next(&mut self) -> Option<Result<(usize, T)>> {
+        let result = self.iter.next()?;
+        Some(result.map(|value| (self.iter.occurrence, value)))
+    }
+}
+
+impl<'a, 'b, I, T> IndexedIter<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>
+{
+    pub (crate) fn new(config: &'a Config<'b, T>, args: I) -> Self {
+        IndexedIter {
+            iter:       Iter::new(config, args),
+        }
+    }
+}
+
+/// The iterator over the successfully processed arguments, which records
+/// errors rather than yielding them.
+///
+/// Created by [`Config::iter_lenient`](struct.Config.html#method.iter_lenient).
+/// After an error, parsing continues with the next option, including the rest
+/// of a cluster of short options after an option whose action failed, unless
+/// [`Config::resync_on_error`](struct.Config.html#method.resync_on_error)
+/// is enabled.
+pub struct LenientIter<'a, 'b: 'a, I, T: 'a>
+    where I: IntoIterator<Item=String>
+{
+    iter:       Iter<'a, 'b, I, T>,
+    errors:     Vec<Error>,
+}
+
+impl<'a, 'b, I, T> fmt::Debug for LenientIter<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>,
+          Iter<'a, 'b, I, T>: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LenientIter")
+            .field("iter",      &self.iter)
+            .field("errors",    &self.errors)
+            .finish()
+    }
+}
+
+impl<'a, 'b, I, T> Iterator for LenientIter<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            match self.iter.next()? {
+                Ok(value) => return Some(value),
+                Err(e)    => self.errors.push(e),
+            }
+        }
+    }
+}
+
+impl<'a, 'b, I, T> LenientIter<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>
+{
+    pub (crate) fn new(config: &'a Config<'b, T>, args: I) -> Self {
+        LenientIter {
+            iter:       Iter::new(config, args),
+            errors:     Vec::new(),
+        }
+    }
+
+    /// The errors recorded so far, in order.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// Warnings recorded so far; see [`Iter::warnings`](struct.Iter.html#method.warnings).
+    pub fn warnings(&self) -> &[String] {
+        self.iter.warnings()
+    }
+}
+
+impl<'a, 'b, I, T> Clone for Iter<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>,
+          I::IntoIter: Clone
+{
+    fn clone(&self) -> Self {
+        let mut iter = Iter {
+            config:     self.config,
+            args:       self.args.clone(),
+            push_back:  self.push_back.clone(),
+            push_back_raw: self.push_back_raw.clone(),
+            pending:    self.pending.clone(),
+            raw_slot:   self.raw_slot.clone(),
+            current_raw: self.current_raw.clone(),
+            in_cluster: self.in_cluster,
+            positional: self.positional,
+            positional_count: self.positional_count,
+            counts:     self.counts.clone(),
+            occurrence: self.occurrence,
+            warnings:   self.warnings.clone(),
+            consumed:   self.consumed,
+            stdin_read: self.stdin_read,
+            exhausted:  self.exhausted,
+            collecting: self.collecting,
+            finishing:  self.finishing,
+            replay:     self.replay.clone(),
+            recording:  None,
+            peeked:     None,
+        };
+
+        if let Some(ref peeked) = self.peeked {
+            iter.restore_snapshot(peeked.before.clone());
+            iter.replay = peeked.taken.iter().chain(&self.replay).cloned().collect();
+        }
+
+        iter
+    }
+}
+
+impl<'a, 'b, I, T> Iter<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>,
+          I::IntoIter: Clone
+{
+    /// Saves the current parsing position, including any partially consumed
+    /// cluster of short options, so that it can be returned to with
+    /// [`restore`](#method.restore).
+    ///
+    /// This clones the underlying argument iterator and the pending cluster;
+    /// the `Config` is shared by reference and is not cloned. A result
+    /// already [peeked](#method.peek) is not cloned either, but parsed again
+    /// by the checkpoint if it gets that far.
+    pub fn checkpoint(&self) -> Self {
+        self.clone()
+    }
+
+    /// Returns to a parsing position saved by
+    /// [`checkpoint`](#method.checkpoint), discarding any progress made since.
+    pub fn restore(&mut self, checkpoint: Self) {
+        *self = checkpoint;
+    }
 }
 
 impl<'a, 'b, I, T> Iter<'a, 'b, I, T>
     where I: IntoIterator<Item=String>
 {
-    fn parse_positional(&self, actual: &str) -> Result<T> {
-        let formal = self.config.get_positional()
-            .ok_or_else(|| Error::from_string("Positional arguments not accepted"))?;
-        formal.parse_argument(actual)
+    /// Takes `actual` as the next positional argument, checking that there
+    /// is a positional to take it.
+    fn take_positional(&mut self, actual: &str, raw: Option<OsString>) -> Step {
+        let index = self.positional_count;
+        self.positional_count += 1;
+        self.occurrence = index;
+
+        if let Some(max) = self.config.get_max_positionals() {
+            if index >= max {
+                return Step::Error(Error::from_string(&format!("too many arguments (max {})", max))
+                    .with_option(actual)
+                    .with_kind(ErrorKind::PositionalNotAccepted));
+            }
+        }
+
+        if self.config.get_positional(index).is_some() {
+            Step::Positional(index, actual.to_owned(), raw)
+        } else if self.config.get_positionals().next().is_some() {
+            Step::Error(Error::from_string(
+                &format!("unexpected argument '{}': too many arguments (max {})",
+                         actual, self.config.positional_capacity()))
+                .with_kind(ErrorKind::PositionalNotAccepted))
+        } else {
+            Step::Error(Error::from_string(
+                &format!("positional arguments not accepted: '{}'", actual))
+                .with_kind(ErrorKind::PositionalNotAccepted))
+        }
+    }
+
+    /// Runs the action of the positional that takes the positional argument
+    /// at `index`.
+    fn run_positional(&self, index: usize, actual: &str, raw: Option<OsString>) -> Result<T> {
+        let formal = self.config.get_positional(index)
+            .expect("Iter::run_positional: taken without a positional");
+        Self::parse_param(formal, actual, raw).map_err(|e| e.with_option(actual))
     }
 }
 
@@ -38,101 +347,657 @@ impl<'a, 'b, I, T> Iterator for Iter<'a, 'b, I, T>
     type Item = Result<T>;
 
     fn next(&mut self) -> Option<Result<T>> {
+        if let Some(peeked) = self.peeked.take() {
+            return peeked.item;
+        }
+
+        if self.exhausted {
+            return None;
+        }
+
+        let result = self.next_item();
+        if self.over_budget() {
+            self.exhausted = true;
+            return Some(Err(limit_exceeded()));
+        }
+        result
+    }
+}
+
+impl<'a, 'b, I, T> Iter<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>
+{
+    fn next_item(&mut self) -> Option<Result<T>> {
+        loop {
+            let result = match self.next_step() {
+                None                                     => return self.next_missing(),
+                Some(Step::EndOfOptions)
+                    | Some(Step::ResumeOptions)          => continue,
+                Some(Step::FromStdin(marker))            => match self.splice_stdin(&marker) {
+                    Ok(())  => continue,
+                    Err(e)  => Err(e),
+                },
+                Some(Step::Option(index, given, param, arg)) => {
+                    let result = self.run_option(index, &given, param, &arg);
+                    self.config.handle_reserved(index);
+                    match result {
+                        Ok(value) => match self.accept(index, &arg) {
+                            Ok(true)  => continue,
+                            Ok(false) => Ok(value),
+                            Err(e)    => Err(e),
+                        },
+                        Err(e)    => Err(e),
+                    }
+                }
+                Some(Step::Value(index, value, raw))     => {
+                    let result = self.run_value(index, &value, raw);
+                    if result.is_ok() && self.record_occurrence(index) {
+                        continue;
+                    }
+                    result
+                }
+                Some(Step::Positional(index, actual, raw)) =>
+                    self.run_positional(index, &actual, raw),
+                Some(Step::Error(e))                     => Err(e),
+            };
+
+            if result.is_err() {
+                self.resync();
+            }
+
+            return Some(result);
+        }
+    }
+
+    /// Takes the next argument, or option with its parameter, and matches it
+    /// against the configuration without running any action. This is the
+    /// part of parsing that [`Config::explain`](struct.Config.html#method.explain)
+    /// shares.
+    pub (crate) fn next_step(&mut self) -> Option<Step> {
         use self::ArgState::*;
 
-        let item = self.push_back.take().or_else(|| self.args.next())?;
+        if let Some(index) = self.collecting.take() {
+            if let Some((value, raw)) = self.next_value() {
+                self.collecting = Some(index);
+                return Some(Step::Value(index, value, raw));
+            }
+        }
+
+        let item = self.next_raw()?;
         let arg  = item.as_str();
 
+        if arg.is_empty() && self.config.rejects_empty_args() {
+            return Some(Step::Error(Error::from_string("empty argument not allowed")));
+        }
+
         if self.positional {
-            return Some(self.parse_positional(arg));
+            if self.config.is_resume_marker(arg) {
+                self.positional = false;
+                return Some(Step::ResumeOptions);
+            }
+
+            let raw = self.current_raw.take();
+            return Some(self.take_positional(arg, raw));
+        }
+
+        if self.config.is_stdin_marker(arg) {
+            if self.stdin_read {
+                return Some(Step::Error(Error::from_string("arguments already read from stdin")
+                    .with_option(arg)));
+            }
+            self.stdin_read = true;
+            return Some(Step::FromStdin(item));
+        }
+
+        if let Some(unescaped) = self.config.unescape(arg) {
+            self.positional = self.config.requires_options_first();
+            let raw = self.current_raw.take()
+                .and_then(|raw| raw_suffix(&raw, arg.len() - unescaped.len()));
+            return Some(self.take_positional(unescaped, raw));
         }
 
-        match analyze_argument(arg) {
-            EndOfOptions          => {
+        let state = self.classify(arg);
+        match state {
+            EndOfOptions  => {
                 self.positional = true;
-                self.args.next().as_ref().map(|s| self.parse_positional(s))
-            }
-
-            ShortOption(c, param) => {
-                let result = if let Some(arg) = self.config.get_short(c) {
-                    if arg.takes_parameter() {
-                        if !param.is_empty() {
-                            arg.parse_argument(param)
-                        } else if let Some(param) = self.args.next() {
-                            arg.parse_argument(&param)
-                        } else {
-                            Err(arg.new_error(false, "expected option parameter"))
-                        }
-                    } else {
-                        if !param.is_empty() {
-                            self.push_back = Some(format!("-{}", param));
-                        }
-                        arg.parse_argument("")
-                    }
-                } else {
-                    Err(Error::from_string("unrecognized").with_option(format!("-{}", c)))
-                };
-
-                Some(result)
-            }
-
-            LongOption(s, param)  => {
-                let result = if let Some(arg) = self.config.get_long(s) {
-                    if arg.takes_parameter() {
-                        if let Some(param) = param {
-                            arg.parse_argument(param)
-                        } else if let Some(param) = self.args.next() {
-                            arg.parse_argument(&param)
-                        } else {
-                            Err(arg.new_error(true, "expected option parameter"))
-                        }
-                    } else if param.is_none() {
-                        arg.parse_argument("")
-                    } else {
-                        Err(arg.new_error(true, "unexpected option parameter"))
-                    }
-                } else {
-                    Err(Error::from_string("unrecognized").with_option(format!("--{}", s)))
-                };
+                return Some(Step::EndOfOptions);
+            }
+            Positional(_) => self.positional = self.config.requires_options_first(),
+            _             => (),
+        }
+
+        Some(self.take_classified(arg, state))
+    }
 
-                Some(result)
+    /// Reads the arguments from stdin for `marker` and makes them the next
+    /// to be processed.
+    fn splice_stdin(&mut self, marker: &str) -> Result<()> {
+        match self.config.read_stdin_args(marker) {
+            Some(Ok(args)) => {
+                for arg in args.into_iter().rev() {
+                    self.pending.push_front(arg);
+                }
+                Ok(())
             }
+            Some(Err(e))   => Err(e),
+            None           => Ok(()),
+        }
+    }
+
+    /// Accepts the option at `index`, taken from argument `arg`, once its
+    /// action has succeeded: reports a conflict with an earlier option, or
+    /// else records the occurrence, returning whether its result should be
+    /// skipped.
+    pub (crate) fn accept(&mut self, index: usize, arg: &str) -> Result<bool> {
+        if let Some(earlier) = self.conflicting(index) {
+            return Err(Error::from_string(&format!("conflicts with {}", earlier))
+                .with_option(arg));
+        }
+        if self.config.get_arg(index).takes_values_until_flag() {
+            self.collecting = Some(index);
+        }
+        Ok(self.record_occurrence(index))
+    }
 
-            Positional(s)         => Some(self.parse_positional(s)),
-        }.map(|o| o.map_err(|e| e.with_option(arg)))
+    /// After an error, drops the rest of the current cluster of short
+    /// options if [`Config::resync_on_error`](struct.Config.html#method.resync_on_error)
+    /// is enabled.
+    pub (crate) fn resync(&mut self) {
+        if self.config.resyncs_on_error() {
+            self.push_back     = None;
+            self.push_back_raw = None;
+        }
     }
 }
 
 impl<'a, 'b, I, T> Iter<'a, 'b, I, T>
     where I: IntoIterator<Item=String>
 {
+    /// Parses the next result without consuming it, so that the following
+    /// call to [`next`](#method.next) returns it. Returns `None` at the end of
+    /// the arguments.
+    pub fn peek(&mut self) -> Option<&Result<T>> {
+        if self.peeked.is_none() {
+            let before     = self.snapshot();
+            self.recording = Some(Vec::new());
+            let item       = self.next();
+            let taken      = self.recording.take().unwrap_or_default();
+            self.peeked    = Some(Peeked { item, before, taken });
+        }
+
+        self.peeked.as_ref().and_then(|peeked| peeked.item.as_ref())
+    }
+
+    /// Saves the parsing state, apart from the underlying iterator.
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            push_back:  self.push_back.clone(),
+            push_back_raw: self.push_back_raw.clone(),
+            in_cluster: self.in_cluster,
+            pending:    self.pending.clone(),
+            current_raw: self.current_raw.clone(),
+            positional: self.positional,
+            positional_count: self.positional_count,
+            counts:     self.counts.clone(),
+            occurrence: self.occurrence,
+            warnings:   self.warnings.clone(),
+            consumed:   self.consumed,
+            stdin_read: self.stdin_read,
+            exhausted:  self.exhausted,
+            collecting: self.collecting,
+            finishing:  self.finishing,
+        }
+    }
+
+    /// Returns to a parsing state saved by [`snapshot`](#method.snapshot).
+    fn restore_snapshot(&mut self, snapshot: Snapshot) {
+        self.push_back  = snapshot.push_back;
+        self.push_back_raw = snapshot.push_back_raw;
+        self.in_cluster = snapshot.in_cluster;
+        self.pending    = snapshot.pending;
+        self.current_raw = snapshot.current_raw;
+        self.positional = snapshot.positional;
+        self.positional_count = snapshot.positional_count;
+        self.counts     = snapshot.counts;
+        self.occurrence = snapshot.occurrence;
+        self.warnings   = snapshot.warnings;
+        self.consumed   = snapshot.consumed;
+        self.stdin_read = snapshot.stdin_read;
+        self.exhausted  = snapshot.exhausted;
+        self.collecting = snapshot.collecting;
+        self.finishing  = snapshot.finishing;
+    }
+
+    /// Warnings recorded so far, such as for repeated
+    /// [`single`](struct.Arg.html#method.single) options.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub (crate) fn config(&self) -> &'a Config<'b, T> {
+        self.config
+    }
+
+    /// Classifies `arg`, the argument last taken by
+    /// [`next_raw`](#method.next_raw), as the rest of a cluster of short
+    /// options if it is one.
+    pub (crate) fn classify<'c>(&self, arg: &'c str) -> ArgState<'c> {
+        if self.in_cluster {
+            self.config.classify_cluster_rest(arg)
+        } else {
+            self.config.classify(arg)
+        }
+    }
+
+    /// Whether more arguments were taken than
+    /// [`Config::max_tokens`](struct.Config.html#method.max_tokens) allows.
+    pub (crate) fn over_budget(&self) -> bool {
+        match self.config.get_max_tokens() {
+            Some(max) => self.consumed > max,
+            None      => false,
+        }
+    }
+
+    /// Gets the next unprocessed argument, including the remainder of a
+    /// partially processed cluster of short options. Each argument counts
+    /// against [`Config::max_tokens`](struct.Config.html#method.max_tokens);
+    /// once that is exceeded, this returns `None`.
+    pub (crate) fn next_raw(&mut self) -> Option<String> {
+        let arg = self.take_raw()?;
+        self.consumed += 1;
+        if self.over_budget() {None} else {Some(arg)}
+    }
+
+    /// Takes the next unprocessed argument for [`next_raw`](#method.next_raw).
+    fn take_raw(&mut self) -> Option<String> {
+        if let Some(arg) = self.push_back.take() {
+            self.current_raw = self.push_back_raw.take();
+            return Some(arg);
+        }
+
+        self.current_raw = None;
+        self.in_cluster  = false;
+        if let Some(arg) = self.pending.pop_front() {
+            return Some(arg);
+        }
+
+        let (arg, raw) = match self.replay.pop_front() {
+            Some(taken) => taken,
+            None        => {
+                let arg = self.args.next()?;
+                (arg, self.raw_slot.as_ref().and_then(|slot| slot.borrow_mut().take()))
+            }
+        };
+        if let Some(ref mut recording) = self.recording {
+            recording.push((arg.clone(), raw.clone()));
+        }
+        self.current_raw = raw;
+        Some(arg)
+    }
+
+    /// Returns `arg`, the argument just taken by
+    /// [`next_raw`](#method.next_raw), to be taken again next.
+    fn unread(&mut self, arg: String) {
+        self.push_back     = Some(arg);
+        self.push_back_raw = self.current_raw.take();
+        self.in_cluster    = false;
+    }
+
+    /// Saves `rest`, the remainder of a cluster started by `lead`, to be
+    /// processed next. The remainder starts at byte `offset` of the current
+    /// argument.
+    fn push_back_rest(&mut self, lead: char, rest: &str, offset: usize) {
+        self.push_back     = Some(format!("{}{}", lead, rest));
+        self.in_cluster    = true;
+        self.push_back_raw = self.current_raw.as_ref()
+            .and_then(|raw| raw_suffix(raw, offset))
+            .map(|suffix| {
+                let mut result = OsString::from(lead.to_string());
+                result.push(suffix);
+                result
+            });
+    }
+
+    /// Runs the action of `formal` on `param`, or on `raw` if the parameter
+    /// is not valid Unicode.
+    fn parse_param(formal: &Arg<'b, T>, param: &str, raw: Option<OsString>) -> Result<T> {
+        match raw {
+            Some(raw) => formal.parse_os_argument(&raw),
+            None      => formal.parse_argument(param),
+        }
+    }
+
+    /// Gets the next unprocessed argument as the detached parameter of an
+    /// option, unless the configuration refuses it, in which case it is left
+    /// to be processed next.
+    fn next_param(&mut self) -> Option<String> {
+        let param = self.next_raw()?;
+        if self.config.refuses_as_param(&param) {
+            self.unread(param);
+            None
+        } else {
+            Some(param)
+        }
+    }
+
+    /// After the arguments run out, yields the count for the next
+    /// [`counted`](struct.Arg.html#method.counted) flag that appeared, or the
+    /// default for the next option that never appeared, or reports it if it
+    /// is [`required`](struct.Arg.html#method.required). Finally, reports a
+    /// positional that took fewer than its
+    /// [`min_count`](struct.Arg.html#method.min_count).
+    fn next_missing(&mut self) -> Option<Result<T>> {
+        while self.finishing < self.counts.len() {
+            let index  = self.finishing;
+            let formal = self.config.get_arg(index);
+            self.finishing  += 1;
+            self.occurrence  = self.counts[index];
+
+            if self.counts[index] > 0 {
+                if let Some(required) = self.missing_requirement(index) {
+                    return Some(Err(Error::from_string(&format!("requires --{}", required))
+                        .with_option(formal.option_name())));
+                }
+
+                match formal.parse_count(self.counts[index]) {
+                    Some(result) => return Some(Ok(result)),
+                    None         => continue,
+                }
+            }
+
+            if formal.is_ignored() {
+                continue;
+            }
+
+            if let Some(default) = formal.get_default() {
+                return Some(formal.parse_argument(default)
+                    .map_err(|e| e.with_option(formal.option_name())));
+            }
+
+            if formal.is_required() {
+                return Some(Err(Error::from_string("required but not supplied")
+                    .with_option(formal.option_name())));
+            }
+        }
+
+        if self.finishing == self.counts.len() {
+            self.finishing += 1;
+            if let Some(formal) = self.config.short_positional(self.positional_count) {
+                return Some(Err(Error::from_string(
+                    &format!("too few arguments (min {})", formal.get_min_count()))
+                    .with_option(formal.positional_name())));
+            }
+        }
+
+        None
+    }
+
+    /// Takes the next argument as a further value of a
+    /// [`values_until_flag`](struct.Arg.html#method.values_until_flag)
+    /// option, unless it looks like an option.
+    fn next_value(&mut self) -> Option<(String, Option<OsString>)> {
+        let item = self.next_raw()?;
+        if let ArgState::Positional(_) = self.config.classify(&item) {
+            let raw = self.current_raw.take();
+            Some((item, raw))
+        } else {
+            self.unread(item);
+            None
+        }
+    }
+
+    /// Runs the action of the option at `index` on a further value.
+    fn run_value(&self, index: usize, value: &str, raw: Option<OsString>) -> Result<T> {
+        let formal = self.config.get_arg(index);
+        Self::parse_param(formal, value, raw).map_err(|e| e.with_option(formal.option_name()))
+    }
+
+    /// The name of an option already seen that conflicts with the option at
+    /// `index`, if any.
+    fn conflicting(&self, index: usize) -> Option<String> {
+        let formal = self.config.get_arg(index);
+        (0 .. self.counts.len())
+            .find(|&other| other != index && self.counts[other] > 0
+                  && formal.conflicts_with_arg(self.config.get_arg(other)))
+            .map(|other| self.config.get_arg(other).option_name())
+    }
+
+    /// The long name of an option required by the option at `index` that was
+    /// not seen, if any.
+    fn missing_requirement(&self, index: usize) -> Option<&'a str> {
+        let config = self.config;
+        config.get_arg(index).get_requires().iter()
+            .find(|required| !(0 .. self.counts.len()).any(|other| self.counts[other] > 0
+                && config.get_arg(other).get_longs().any(|long| long == required.as_str())))
+            .map(String::as_str)
+    }
+
+    /// Records a successful occurrence of the argument at `index`, returning
+    /// whether its result should be skipped.
+    fn record_occurrence(&mut self, index: usize) -> bool {
+        let formal = self.config.get_arg(index);
+        self.counts[index] += 1;
+        self.occurrence = self.counts[index] - 1;
+
+        if formal.is_single() && self.counts[index] == 2 {
+            self.warnings.push(format!("option {} given more than once; using last",
+                                       formal.option_name()));
+        }
+
+        formal.is_ignored() || formal.is_counted()
+    }
+
+    /// Parses argument `arg`, which was classified as `state`, returning the
+    /// index of the formal argument that it matched, if any, along with the
+    /// result.
+    pub (crate) fn parse_classified(&mut self, arg: &str, state: ArgState)
+        -> (Option<usize>, Result<T>)
+    {
+        match self.take_classified(arg, state) {
+            Step::Option(index, given, param, arg) =>
+                (Some(index), self.run_option(index, &given, param, &arg)),
+            Step::Positional(index, actual, raw)   =>
+                (None, self.run_positional(index, &actual, raw)),
+            Step::Error(e)                         => (None, Err(e)),
+            step                                   =>
+                unreachable!("Iter::parse_classified: {:?}", step),
+        }
+    }
+
+    /// Matches argument `arg`, which was classified as `state`, against the
+    /// configuration, taking the parameter of an option that needs one.
+    fn take_classified(&mut self, arg: &str, state: ArgState) -> Step {
+        use self::ArgState::*;
+
+        match state {
+            EndOfOptions          =>
+                Step::Error(Error::from_string("unexpected end of options").with_option(arg)),
+
+            ShortOption(c, param) => match self.config.short_index(c) {
+                Some(index) => self.take_short(index, arg, c, param),
+                None        => Step::Error(Error::unknown_flag(format!("-{}", c))),
+            },
+
+            PlusOption(c, rest)   => {
+                if !rest.is_empty() {
+                    self.push_back_rest('+', rest, 1 + c.len_utf8());
+                }
+                match self.config.short_index(c) {
+                    Some(index) if self.config.get_arg(index).is_toggle() =>
+                        Step::Option(index, Given::Plus(c), Param::Flag, arg.to_owned()),
+                    Some(_)     => Step::Error(Error::from_string("not a toggle")
+                        .with_option(format!("+{}", c))),
+                    None        => Step::Error(Error::unknown_flag(format!("+{}", c))),
+                }
+            }
+
+            LongOption("", _)     =>
+                Step::Error(Error::from_string("empty option name").with_option(arg)),
+
+            LongOption(s, param)  => match self.config.lookup_long(s) {
+                Ok(index) => self.take_long(index, arg, s, param),
+                Err(e)    => Step::Error(e),
+            },
+
+            Positional(s)         => {
+                let raw = self.current_raw.take();
+                self.take_positional(s, raw)
+            }
+        }
+    }
+
+    /// Takes short option `c` at `index` from argument `arg`, where `param`
+    /// is the remainder of `arg` after `c`.
+    fn take_short(&mut self, index: usize, arg: &str, c: char, param: &str) -> Step {
+        let formal = self.config.get_arg(index);
+        let param  = if formal.takes_parameter() {
+            if !param.is_empty() && self.config.is_ambiguous_cluster(param) {
+                return Step::Error(Error::from_string(&format!(
+                    "ambiguous: takes a parameter but is followed by options in '{}'; \
+                     write -{} {} to pass '{}' as its parameter",
+                    arg, c, param, param)).with_option(format!("-{}", c)));
+            } else if !param.is_empty() {
+                let raw = self.current_raw.as_ref()
+                    .and_then(|raw| raw_suffix(raw, 1 + c.len_utf8()));
+                Param::Value(param.to_owned(), raw)
+            } else if formal.takes_optional_parameter() {
+                Param::Bare
+            } else if let Some(param) = self.next_param() {
+                Param::Value(param, self.current_raw.take())
+            } else {
+                return Step::Error(Error::missing_param(format!("-{}", c)));
+            }
+        } else {
+            if !param.is_empty() {
+                self.push_back_rest('-', param, 1 + c.len_utf8());
+            }
+            Param::Flag
+        };
+
+        Step::Option(index, Given::Short(c), param, arg.to_owned())
+    }
+
+    /// Takes long option `s` at `index` from argument `arg`, where `param`
+    /// is the part of `arg` after `=`, if any.
+    fn take_long(&mut self, index: usize, arg: &str, s: &str, param: Option<&str>) -> Step {
+        let formal = self.config.get_arg(index);
+        let param  = if formal.takes_parameter() {
+            if let Some(param) = param {
+                let raw = self.current_raw.as_ref().and_then(|raw| raw_after_eq(raw));
+                Param::Value(param.to_owned(), raw)
+            } else if formal.takes_optional_parameter() {
+                Param::Bare
+            } else if let Some(param) = self.next_param() {
+                Param::Value(param, self.current_raw.take())
+            } else {
+                return Step::Error(Error::missing_param(format!("--{}", s)));
+            }
+        } else if let Some(param) = param {
+            return Step::Error(Error::unexpected_param(format!("--{}", s), param));
+        } else {
+            Param::Flag
+        };
+
+        Step::Option(index, Given::Long(s.to_owned()), param, arg.to_owned())
+    }
+
+    /// Runs the action of the option at `index`, given as `given` in
+    /// argument `arg`, on its parameter. A flag given as a short option runs
+    /// its action, while a toggle given as `+c` or by its long name is
+    /// switched on.
+    fn run_option(&self, index: usize, given: &Given, param: Param, arg: &str) -> Result<T> {
+        let formal = self.config.get_arg(index);
+        let result = match param {
+            Param::Value(param, raw) => Self::parse_param(formal, &param, raw),
+            Param::Bare              => formal.parse_bare(),
+            Param::Flag              => match *given {
+                Given::Short(_) => formal.parse_argument(""),
+                _               => formal.parse_plus(),
+            },
+        };
+
+        result.map_err(|e| e.with_option(arg))
+    }
+}
+
+/// The error for arguments beyond
+/// [`Config::max_tokens`](struct.Config.html#method.max_tokens).
+pub (crate) fn limit_exceeded() -> Error {
+    Error::from_string("argument limit exceeded")
+}
+
+impl<'a, 'b, I, T> Iter<'a, 'b, I, T>
+    where I: IntoIterator<Item=String>
+{
+    /// Makes the iterator take the raw form of arguments that are not valid
+    /// Unicode from `slot`, where [`OsArgs`](struct.OsArgs.html) leaves it.
+    pub (crate) fn with_raw_slot(mut self, slot: RawSlot) -> Self {
+        self.raw_slot = Some(slot);
+        self
+    }
+
     /// Creates a new `foropts::Iter` from a reference to the
     /// configuration and an iterator over the unparsed arguments.
     pub (crate) fn new(config: &'a Config<'b, T>, args: I) -> Self {
+        let mut args = args.into_iter();
+        let pending  = config.preprocess_args(&mut args)
+            .map(VecDeque::from)
+            .unwrap_or_default();
+
         Iter {
             config,
-            args:       args.into_iter(),
+            args,
             push_back:  None,
+            push_back_raw: None,
+            in_cluster: false,
+            pending,
+            raw_slot:   None,
+            current_raw: None,
             positional: false,
+            positional_count: 0,
+            counts:     vec![0; config.arg_count()],
+            occurrence: 0,
+            warnings:   Vec::new(),
+            consumed:   0,
+            stdin_read: false,
+            exhausted:  false,
+            collecting: None,
+            finishing:  0,
+            replay:     VecDeque::new(),
+            recording:  None,
+            peeked:     None,
         }
     }
 }
 
-enum ArgState<'a> {
+/// The syntactic classification of an unprocessed argument.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArgState<'a> {
+    /// The end-of-options marker `--`.
     EndOfOptions,
+    /// A short option and the rest of its argument, as in `-o` or `-ofile`.
     ShortOption(char, &'a str),
+    /// A short option given with `+` and the rest of its argument, as in
+    /// `+x` or `+xy`, when
+    /// [`Config::plus_minus_pairs`](struct.Config.html#method.plus_minus_pairs)
+    /// is enabled.
+    PlusOption(char, &'a str),
+    /// A long option and its `=`-separated parameter, as in `--out` or
+    /// `--out=file`.
     LongOption(&'a str, Option<&'a str>),
+    /// A positional argument.
     Positional(&'a str),
 }
 
-fn analyze_argument(param: &str) -> ArgState {
+pub (crate) fn analyze_argument(param: &str) -> ArgState<'_> {
     match split_first_str(param) {
         Some(('-', rest)) => analyze_option(rest),
         _ => ArgState::Positional(param)
     }
 }
 
-fn analyze_option(opt: &str) -> ArgState {
+fn analyze_option(opt: &str) -> ArgState<'_> {
     use self::ArgState::*;
 
     match split_first_str(opt) {
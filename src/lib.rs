@@ -70,22 +70,48 @@
 
 use std::str::FromStr;
 
+#[cfg(feature = "unicode-normalization")]
+extern crate unicode_normalization;
+
 mod util;
 
 mod arg;
+mod command;
+mod completion;
 mod config;
+mod cursor;
 mod error;
+mod explain;
 mod iter;
+mod lines;
+mod os;
+mod quick;
+mod stdin;
+mod struct_parser;
 
 pub use arg::Arg;
-pub use config::Config;
-pub use error::{Error, Result};
-pub use iter::Iter;
+pub use command::to_command;
+pub use completion::{CompletionModel, OptionCompletion, PositionalCompletion};
+pub use config::{Config, HelpStream};
+#[cfg(feature = "unicode-normalization")]
+pub use config::Form;
+pub use cursor::Cursor;
+pub use error::{Error, ErrorKind, Result};
+pub use explain::Explanation;
+pub use iter::{ArgState, IndexedIter, Iter, LenientIter};
+pub use lines::{parse_lines, LineIter};
+pub use os::OsArgs;
+pub use quick::{parse_shorts, quick_parse};
+pub use struct_parser::StructParser;
 
 #[cfg(test)]
 mod tests {
-    use super::{Config, Arg, Result};
+    use super::{Config, Arg, ArgState, CompletionModel, Error, ErrorKind, Explanation, HelpStream,
+                OptionCompletion, PositionalCompletion, Result, StructParser, to_command,
+                parse_lines, parse_shorts, quick_parse};
     use std::fmt::Debug;
+    use std::{env, io};
+    use std::process::Command;
 
     #[test]
     fn char_example() {
@@ -99,6 +125,7 @@ mod tests {
         assert_eq!( Ok("abba".to_owned()), result );
     }
 
+    #[allow(clippy::upper_case_acronyms)]
     #[derive(PartialEq, Debug)]
     enum FLS {
         Freq(f32),
@@ -155,10 +182,284 @@ mod tests {
         assert_parse(config, &["--freq", "5.5"], &[FLS::Freq(5.5)]);
     }
 
+    #[test]
+    fn parsed_param_error_names_type() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Port(u16), Name(String), Ratio(f64) }
+
+        let config = &Config::new("types")
+            .arg(Arg::parsed_param("port", Opt::Port).short('p'))
+            .arg(Arg::parsed_param("NAME", Opt::Name).short('n'))
+            .arg(Arg::parsed_param("RATIO", Opt::Ratio).short('r').type_name("number"))
+            .arg(Arg::str_param("WHEN", |s| if s == "now" {Ok(Opt::Ratio(0.0))} else {
+                     Err(Error::from_string("expected now").with_kind(ErrorKind::ParseFailure))
+                 }).short('w').type_name("time"));
+
+        assert_parse_error_matches(config, &["-p", "x"],
+                                   "option -p: invalid value for <port:u16>: 'x' (invalid digit found in string)");
+        assert_parse_error_matches(config, &["-p70000"], "invalid value for <port:u16>: '70000'");
+        assert_parse_error_matches(config, &["-r", "half"], "option -r: invalid value for <RATIO:number>: 'half'");
+        assert_parse_error_matches(config, &["-w", "later"],
+                                   "option -w: invalid value for <WHEN:time>: 'later' (expected now)");
+        assert_parse(config, &["-nx"], &[Opt::Name("x".to_owned())]);
+    }
+
     #[test]
     fn float_parsing_error_message() {
         assert_parse_error_matches(&fls_config(), &["-fhello"],
-                                   "option -fhello: invalid float literal");
+                                   "option -fhello: invalid value for <FREQ:f32>: 'hello' (invalid float literal)");
+    }
+
+    #[test]
+    fn parse_into_reuses_vec() {
+        let config = fls_config();
+        let mut out = Vec::with_capacity(8);
+
+        config.parse_into(vec!["-sl".to_owned()], &mut out, true).unwrap();
+        assert_eq!( vec![FLS::Softer, FLS::Louder], out );
+
+        config.parse_into(vec!["-s".to_owned()], &mut out, false).unwrap();
+        assert_eq!( vec![FLS::Softer, FLS::Louder, FLS::Softer], out );
+
+        config.parse_into(vec!["-l".to_owned()], &mut out, true).unwrap();
+        assert_eq!( vec![FLS::Louder], out );
+
+        assert!( config.parse_into(vec!["-s".to_owned(), "-x".to_owned()],
+                                   &mut out, true).is_err() );
+        assert_eq!( vec![FLS::Softer], out );
+    }
+
+    #[test]
+    fn extend_config() {
+        let mut config = Config::new("fls")
+            .arg(Arg::flag(|| FLS::Louder).short('l'));
+        config.extend(vec![
+            Arg::flag(|| FLS::Softer).short('s'),
+            Arg::parsed_param("FREQ", FLS::Freq).long("freq"),
+        ]);
+
+        assert_parse(&config, &["-ls", "--freq=1"],
+                     &[FLS::Louder, FLS::Softer, FLS::Freq(1.0)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn extend_config_repeated() {
+        let mut config = fls_config();
+        config.extend(vec![Arg::flag(|| FLS::Softer).short('s')]);
+    }
+
+    #[test]
+    fn short_case_insensitive() {
+        let config = &fls_config().short_case_insensitive(true);
+        assert_parse(config, &["-Sl", "-F2"], &[FLS::Softer, FLS::Louder, FLS::Freq(2.0)]);
+        assert_parse_error(&fls_config(), &["-S"]);
+    }
+
+    #[test]
+    fn short_case_insensitive_turned_off() {
+        let config = &Config::new("fold")
+            .arg(Arg::flag(|| 'V').short('V'))
+            .arg(Arg::flag(|| 'q').short('q'))
+            .short_case_insensitive(true)
+            .short_case_insensitive(false);
+        assert_parse(config, &["-Vq"], &['V', 'q']);
+        assert_parse_error(config, &["-v"]);
+    }
+
+    #[test]
+    fn short_case_insensitive_repeated() {
+        let mut config = Config::new("fold").short_case_insensitive(true);
+        assert!( config.arg_safe(Arg::flag(|| 'a').short('a')).is_ok() );
+        assert_eq!( Err("option -A: repeated in config".to_owned()),
+                    config.arg_safe(Arg::flag(|| 'A').short('A')).map_err(|e| e.to_string()) );
+    }
+
+    #[test]
+    #[should_panic]
+    fn short_case_insensitive_after_repeated() {
+        Config::new("fold")
+            .arg(Arg::flag(|| 'a').short('a'))
+            .arg(Arg::flag(|| 'A').short('A'))
+            .short_case_insensitive(true);
+    }
+
+    #[test]
+    fn structurally_eq_configs() {
+        let mut built = Config::new("fls");
+        built.extend(vec![
+            Arg::parsed_param("FREQ", |f: f32| FLS::Freq(f * 2.0)).long("freq").short('f'),
+            Arg::flag(|| FLS::Softer).short('l').long("louder"),
+        ]);
+        built.arg_safe(Arg::flag(|| FLS::Louder).short('s').long("softer")).unwrap();
+
+        assert!( fls_config().structurally_eq(&built) );
+        assert!( !fls_config().structurally_eq(&built.version("1.0")) );
+        assert!( !fls_config().structurally_eq(
+            &fls_config().arg(Arg::flag(|| FLS::Louder).short('L'))) );
+        assert!( !fls_config().structurally_eq(
+            &Config::new("fls")
+                .arg(Arg::parsed_param("HZ", FLS::Freq).short('f').long("freq"))
+                .arg(Arg::flag(|| FLS::Louder).short('l').long("louder"))
+                .arg(Arg::flag(|| FLS::Softer).short('s').long("softer"))) );
+    }
+
+    #[test]
+    fn args_from_stdin_splices() {
+        let config = &fls_config()
+            .args_from_stdin(true)
+            .stdin_reader(io::Cursor::new("-l --freq\n 3\n"));
+        assert_parse(config, &["-s", "-", "-s"],
+                     &[FLS::Softer, FLS::Louder, FLS::Freq(3.0), FLS::Softer]);
+        assert_parse(config, &["-"], &[FLS::Louder, FLS::Freq(3.0)]);
+    }
+
+    #[test]
+    fn args_from_stdin_replayed_by_checkpoint() {
+        let config = fls_config().stdin_reader(io::Cursor::new("-l -s"));
+        let mut iter = config.iter(vec!["-f1".to_owned(), "-".to_owned()]);
+
+        assert_eq!( Some(Ok(FLS::Freq(1.0))), iter.next() );
+        let checkpoint = iter.checkpoint();
+        let rest: Vec<_> = iter.by_ref().collect();
+        assert_eq!( vec![Ok(FLS::Louder), Ok(FLS::Softer)], rest );
+
+        iter.restore(checkpoint);
+        assert_eq!( rest, iter.collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn args_from_stdin_only_once() {
+        let config = &fls_config()
+            .stdin_marker("@-")
+            .stdin_reader(io::Cursor::new("-l"));
+        assert_parse_error_matches(config, &["@-", "-s", "@-"],
+                                   "option @-: arguments already read from stdin");
+        assert_parse_error_matches(&fls_config(), &["-"],
+                                   "positional arguments not accepted: '-'");
+    }
+
+    #[test]
+    fn explain_follows_iter_modes() {
+        use super::Explanation::{Short, Long, Positional, EndOfOptions, ResumeOptions, FromStdin};
+
+        let explain = |config: &Config<Pos>, args: &[&str]|
+            config.explain(args.iter().map(ToString::to_string));
+        let config = || pos_config()
+            .arg(Arg::str_param("OUT", |_| Ok(Pos::FlagA)).short('o').long("out"));
+
+        let strict = config().strict_value_consumption(true);
+        assert_eq!( vec![Explanation::Error(Error::missing_param("--out")),
+                         Short('a', None)],
+                    explain(&strict, &["--out", "-a"]) );
+
+        let last = config().value_short_must_be_last(true);
+        assert_parse_error(&last, &["-oa"]);
+        assert!( match explain(&last, &["-oa"])[..] {
+            [Explanation::Error(ref e)] => e.to_string().contains("ambiguous"),
+            _                           => false,
+        } );
+
+        let first = config().require_options_first().resume_options_marker("++");
+        assert_eq!( vec![Positional("f".to_owned()), ResumeOptions, Short('a', None),
+                         EndOfOptions, Positional("-a".to_owned())],
+                    explain(&first, &["f", "++", "-a", "--", "-a"]) );
+
+        let max = config().max_positionals(1);
+        assert_eq!( vec![Positional("x".to_owned()),
+                         Explanation::Error(Error::from_string("too many arguments (max 1)")
+                             .with_option("y").with_kind(ErrorKind::PositionalNotAccepted))],
+                    explain(&max, &["x", "y"]) );
+
+        let empty = config().reject_empty_args(true);
+        assert_eq!( vec![Explanation::Error(Error::from_string("empty argument not allowed")),
+                         Long("out".to_owned(), Some("".to_owned()))],
+                    explain(&empty, &["", "--out="]) );
+
+        let stdin = config().args_from_stdin(true).stdin_reader(io::Cursor::new("-a"));
+        assert_eq!( vec![FromStdin("-".to_owned()), EndOfOptions],
+                    explain(&stdin, &["-", "--"]) );
+
+        let budget = config().max_tokens(2);
+        assert_eq!( vec![Short('a', None), Short('a', None),
+                         Explanation::Error(Error::from_string("argument limit exceeded"))],
+                    explain(&budget, &["-a", "-a", "-a"]) );
+    }
+
+    #[test]
+    fn explain_command_line() {
+        use super::Explanation::{Short, Long, Positional, EndOfOptions};
+
+        let config = fls_config()
+            .arg(Arg::optional_param("LEVEL", || FLS::Louder, |_| Ok(FLS::Louder)).short('c'))
+            .arg(Arg::parsed_param("FILE", |_: String| FLS::Softer));
+        let args = ["-slf", "2", "--freq=3", "-c", "file", "-x", "--louder=yes",
+                    "--", "-s"];
+
+        assert_eq!( vec![Short('s', None),
+                         Short('l', None),
+                         Short('f', Some("2".to_owned())),
+                         Long("freq".to_owned(), Some("3".to_owned())),
+                         Short('c', None),
+                         Positional("file".to_owned()),
+                         Explanation::Error(Error::unknown_flag("-x")),
+                         Explanation::Error(Error::unexpected_param("--louder", "yes")),
+                         EndOfOptions,
+                         Positional("-s".to_owned())],
+                    config.explain(args.iter().map(ToString::to_string)) );
+        assert_eq!( vec![Explanation::Error(Error::missing_param("--freq"))],
+                    config.explain(vec!["--freq".to_owned()]) );
+    }
+
+    #[test]
+    fn value_short_must_be_last() {
+        let config = &Config::new("out")
+            .arg(Arg::parsed_param("FILE", Pos::Positional).short('o'))
+            .arg(Arg::flag(|| Pos::FlagA).short('a'));
+
+        assert_parse(config, &["-oa"], &[Pos::Positional("a".to_owned())]);
+
+        let strict = &Config::new("out")
+            .arg(Arg::parsed_param("FILE", Pos::Positional).short('o'))
+            .arg(Arg::flag(|| Pos::FlagA).short('a'))
+            .value_short_must_be_last(true);
+
+        assert_parse_error_matches(strict, &["-oa"],
+                                   "option -o: ambiguous: takes a parameter but is \
+                                    followed by options in '-oa'; write -o a");
+        assert_parse(strict, &["-ao", "a"], &[Pos::FlagA, Pos::Positional("a".to_owned())]);
+        assert_parse(strict, &["-ofile"], &[Pos::Positional("file".to_owned())]);
+    }
+
+    #[test]
+    fn preprocess_expands_alias() {
+        let config = &fls_config().preprocess(|args| {
+            args.into_iter()
+                .flat_map(|arg| if arg == "ll" {
+                    vec!["-l".to_owned(), "-s".to_owned()]
+                } else {
+                    vec![arg]
+                })
+                .collect()
+        });
+
+        assert_parse(config, &["-s", "ll", "-s"],
+                     &[FLS::Softer, FLS::Louder, FLS::Softer, FLS::Softer]);
+    }
+
+    #[test]
+    fn resync_on_error() {
+        let args   = || ["-Xl", "-nl", "-s"].iter().map(ToString::to_string);
+        let failed = || Error::from_string("failed").with_option("-nl");
+        let config = || fls_config().arg(Arg::str_param("", |_| Err(Error::from_string("failed")))
+                                             .short('n'));
+
+        assert_eq!( vec![Err(Error::unknown_flag("-X")), Err(failed()), Ok(FLS::Louder),
+                         Ok(FLS::Softer)],
+                    config().iter(args()).collect::<Vec<_>>() );
+
+        assert_eq!( vec![Err(Error::unknown_flag("-X")), Err(failed()), Ok(FLS::Softer)],
+                    config().resync_on_error(true).iter(args()).collect::<Vec<_>>() );
     }
 
     fn fls_config() -> Config<'static, FLS> {
@@ -189,6 +490,29 @@ mod tests {
                        Pos::FlagA]);
     }
 
+    #[test]
+    fn error_constructors_match_iter() {
+        let config = &fls_config();
+        assert_eq!( Err(Error::unknown_flag("-x")), parse(config, &["-sx"]) );
+        assert_eq!( Err(Error::unknown_flag("--loud")), parse(config, &["--loud"]) );
+        assert_eq!( Err(Error::missing_param("-f")), parse(config, &["-lf"]) );
+        assert_eq!( Err(Error::missing_param("--freq")), parse(config, &["--freq"]) );
+        assert_eq!( Err(Error::unexpected_param("--louder", "yes")),
+                    parse(config, &["--louder=yes"]) );
+
+        assert_eq!( "option -x: unrecognized", Error::unknown_flag("-x").to_string() );
+        assert_eq!( "option --freq: expected option parameter",
+                    Error::missing_param("--freq").to_string() );
+        assert_eq!( "option --louder: unexpected option parameter 'yes'",
+                    Error::unexpected_param("--louder", "yes").to_string() );
+    }
+
+    #[test]
+    fn empty_long_option_name() {
+        assert_parse_error_matches(&pos_config(), &["--=x"], "option --=x: empty option name");
+        assert_parse_error_matches(&pos_config(), &["--="], "option --=: empty option name");
+    }
+
     #[test]
     fn unrecognized_option_works() {
         assert_parse_error_matches(&pos_config(),
@@ -196,12 +520,1486 @@ mod tests {
                                    "option -b: unrecognized");
     }
 
+    #[test]
+    fn resume_options_marker() {
+        let config = &pos_config().resume_options_marker("++");
+        assert_parse(config, &["--", "file", "++", "-a"],
+                     &[Pos::Positional("file".to_owned()), Pos::FlagA]);
+        assert_parse(config, &["--", "++", "-a", "--", "-a"],
+                     &[Pos::FlagA, Pos::Positional("-a".to_owned())]);
+        assert_parse(&pos_config(), &["--", "file", "++", "-a"],
+                     &[Pos::Positional("file".to_owned()),
+                       Pos::Positional("++".to_owned()),
+                       Pos::Positional("-a".to_owned())]);
+    }
+
+    #[test]
+    fn positional_not_accepted() {
+        let config = &Config::new("flags_only").arg(Arg::flag(|| Pos::FlagA).short('a'));
+        assert_parse_error_matches(config, &["-a", "file"],
+                                   "positional arguments not accepted: 'file'");
+        assert_parse_error_matches(config, &["--", "file"],
+                                   "positional arguments not accepted: 'file'");
+    }
+
+    #[test]
+    fn positional_oversupplied() {
+        let config = &Config::new("clone")
+            .arg(Arg::flag(String::new).short('v'))
+            .arg(Arg::str_param("REPO", |s| Ok(s.to_owned())))
+            .arg(Arg::str_param("DIR", |s| Ok(s.to_owned())).max_count(1));
+
+        assert_parse(config, &["url", "-v", "dir"], &["url".to_owned(), String::new(),
+                                                      "dir".to_owned()]);
+        assert_eq!( Err(Error::from_string("unexpected argument 'x': too many arguments (max 2)")
+                        .with_kind(ErrorKind::PositionalNotAccepted)),
+                    parse(config, &["url", "dir", "x"]) );
+        assert_parse_error_matches(config, &["url", "dir", "--", "-v"],
+                                   "unexpected argument '-v'");
+    }
+
+    #[test]
+    fn positional_parse_error_names_argument() {
+        let config = &Config::new("floats").arg(Arg::parsed_param("X", FLS::Freq));
+        assert_parse_error_matches(config, &["1", "x"],
+                                   "option x: invalid value for <X:f32>: 'x'");
+        assert_parse_error_matches(config, &["--", "x"],
+                                   "option x: invalid value for <X:f32>: 'x'");
+    }
+
+    #[test]
+    fn ignored_option_yields_nothing() {
+        let config = &pos_config()
+            .arg(Arg::parsed_param("X", Pos::Positional).long("legacy-flag").ignored());
+        assert_parse(config, &["--legacy-flag", "foo", "-a"], &[Pos::FlagA]);
+        assert_parse(config, &["bar", "--legacy-flag=foo"],
+                     &[Pos::Positional("bar".to_owned())]);
+        assert_parse_error(config, &["--legacy-flag"]);
+    }
+
+    #[test]
+    fn escape_char_makes_positional() {
+        let config = &pos_config().escape_char('\\');
+        assert_parse(config, &["\\--", "file", "-a"],
+                     &[Pos::Positional("--".to_owned()),
+                       Pos::Positional("file".to_owned()),
+                       Pos::FlagA]);
+        assert_parse(config, &["\\-a", "\\x"],
+                     &[Pos::Positional("-a".to_owned()),
+                       Pos::Positional("\\x".to_owned())]);
+        assert_parse(config, &["--", "\\--"],
+                     &[Pos::Positional("\\--".to_owned())]);
+        assert_parse_error(&pos_config(), &["\\--", "-b"]);
+    }
+
+    #[test]
+    fn positional_parsed_names_bad_value() {
+        let config = &Config::new("sum").positional_parsed("N", |n: u32| n);
+        assert_parse(config, &["1", "2", "3"], &[1, 2, 3]);
+        assert_parse_error_matches(config, &["1", "2x", "3"],
+                                   "option 2x: invalid value for <N:u32>: '2x' (invalid digit");
+        assert_parse_error_matches(config, &["1", "-2"], "option -2: unrecognized");
+    }
+
+    #[test]
+    fn single_option_warns_when_repeated() {
+        let config = Config::new("out")
+            .arg(Arg::parsed_param("FILE", Pos::Positional).short('o').long("out").single())
+            .arg(Arg::flag(|| Pos::FlagA).short('a'));
+        let args = ["--out", "x", "-a", "-oy", "-a", "--out=z"].iter().map(ToString::to_string);
+        let mut iter = config.iter(args);
+
+        let mut out = None;
+        for opt in &mut iter {
+            if let Pos::Positional(file) = opt.unwrap() {
+                out = Some(file);
+            }
+        }
+
+        assert_eq!( Some("z".to_owned()), out );
+        assert_eq!( &["option --out given more than once; using last".to_owned()],
+                    iter.warnings() );
+    }
+
+    #[test]
+    fn empty_args() {
+        assert_parse(&pos_config(), &["-a", "", "--", ""],
+                     &[Pos::FlagA,
+                       Pos::Positional(String::new()),
+                       Pos::Positional(String::new())]);
+
+        let config = &pos_config().reject_empty_args(true);
+        assert_parse_error_matches(config, &["-a", ""], "empty argument not allowed");
+        assert_parse_error_matches(config, &["--", ""], "empty argument not allowed");
+        assert_parse(config, &["-a", "x"], &[Pos::FlagA, Pos::Positional("x".to_owned())]);
+    }
+
+    #[test]
+    fn end_of_options_marker() {
+        let config = &pos_config().end_of_options_marker("::");
+        assert_parse(config, &["-a", "::", "-a"],
+                     &[Pos::FlagA, Pos::Positional("-a".to_owned())]);
+        assert_parse(config, &["--", "-a"],
+                     &[Pos::Positional("--".to_owned()), Pos::FlagA]);
+        assert_parse(config, &["::x", "-a"],
+                     &[Pos::Positional("::x".to_owned()), Pos::FlagA]);
+        assert!( usage(config).contains("Usage: pos OPTION... [::] POS...\n") );
+    }
+
+    #[test]
+    fn max_positionals() {
+        let config = &pos_config().max_positionals(2);
+        assert_parse(config, &["x", "-a", "y"],
+                     &[Pos::Positional("x".to_owned()),
+                       Pos::FlagA,
+                       Pos::Positional("y".to_owned())]);
+        assert_parse_error_matches(config, &["x", "y", "-a", "z"],
+                                   "option z: too many arguments (max 2)");
+        assert_parse_error_matches(config, &["x", "--", "y", "z"],
+                                   "option z: too many arguments (max 2)");
+    }
+
+    #[test]
+    fn positional_min_and_max_count() {
+        let config = &Config::new("pos")
+            .arg(Arg::flag(|| Pos::FlagA).short('a'))
+            .arg(Arg::parsed_param("POS", Pos::Positional).min_count(1).max_count(3));
+        let pos = |s: &str| Pos::Positional(s.to_owned());
+
+        assert_parse(config, &["w", "x", "-a", "y"], &[pos("w"), pos("x"), Pos::FlagA, pos("y")]);
+        assert_eq!( vec![Ok(pos("w")), Ok(pos("x")), Ok(pos("y")),
+                         Err(Error::from_string("unexpected argument 'z': too many arguments (max 3)")
+                             .with_kind(ErrorKind::PositionalNotAccepted)),
+                         Ok(Pos::FlagA)],
+                    config.iter(vec!["w", "x", "y", "z", "-a"].into_iter().map(String::from))
+                        .collect::<Vec<_>>() );
+        assert_eq!( vec![Ok(Pos::FlagA),
+                         Err(Error::from_string("too few arguments (min 1)").with_option("POS"))],
+                    config.iter(vec!["-a".to_owned()]).collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn positional_counts_per_slot() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Src(String), Dest(String) }
+
+        let config = &Config::new("copy")
+            .arg(Arg::str_param("SRC", |s| Ok(Opt::Src(s.to_owned()))).min_count(1))
+            .arg(Arg::str_param("DEST", |s| Ok(Opt::Dest(s.to_owned())))
+                 .min_count(1).max_count(3));
+        let dest = |s: &str| Opt::Dest(s.to_owned());
+
+        assert_parse(config, &["a", "b", "c", "d"],
+                     &[Opt::Src("a".to_owned()), dest("b"), dest("c"), dest("d")]);
+        assert_parse_error_matches(config, &["a"], "option DEST: too few arguments (min 1)");
+        assert_parse_error_matches(config, &[], "option SRC: too few arguments (min 1)");
+        assert_parse_error_matches(config, &["a", "b", "c", "d", "e"],
+                                   "unexpected argument 'e': too many arguments (max 4)");
+    }
+
+    #[test]
+    fn several_positionals() {
+        #[derive(PartialEq, Debug)]
+        enum CloneCmd { Verbose, Repo(String), Dir(String) }
+
+        let config = Config::new("clone")
+            .arg(Arg::flag(|| CloneCmd::Verbose).short('v'))
+            .arg(Arg::str_param("REPO", |s| Ok(CloneCmd::Repo(s.to_owned()))))
+            .arg(Arg::str_param("DIR", |s| Ok(CloneCmd::Dir(s.to_owned()))));
+
+        assert_parse(&config, &["url", "-v", "a", "b"],
+                     &[CloneCmd::Repo("url".to_owned()), CloneCmd::Verbose,
+                       CloneCmd::Dir("a".to_owned()), CloneCmd::Dir("b".to_owned())]);
+        assert_parse(&config.max_positionals(2), &["url", "--", "-v"],
+                     &[CloneCmd::Repo("url".to_owned()), CloneCmd::Dir("-v".to_owned())]);
+        assert_parse_error_matches(&Config::new("clone").max_positionals(2)
+                                       .arg(Arg::str_param("REPO", |s| Ok(s.to_owned())))
+                                       .arg(Arg::str_param("DIR", |s| Ok(s.to_owned()))),
+                                   &["a", "b", "c"], "option c: too many arguments (max 2)");
+        assert!( usage(&Config::new("clone")
+                       .arg(Arg::str_param("REPO", |s| Ok(s.to_owned())))
+                       .arg(Arg::str_param("DIR", |s| Ok(s.to_owned()))))
+                 .contains("Usage: clone OPTION... [--] REPO DIR...\n\nARGS:\n  REPO\n  DIR\n") );
+    }
+
+    #[test]
+    fn option_groups() {
+        let config = Config::new("groups")
+            .arg(Arg::flag(|| ()).long("in").group("Input options"))
+            .arg(Arg::flag(|| ()).long("out").group("Output options"))
+            .arg(Arg::flag(|| ()).long("quiet"))
+            .arg(Arg::flag(|| ()).long("stdin").group("Input options"));
+
+        assert!( usage(&config).ends_with("\nOPTIONS:\n  --quiet\n\
+                                           \nInput options:\n  --in\n  --stdin\n\
+                                           \nOutput options:\n  --out\n") );
+        assert!( usage(&Config::new("groups")
+                       .arg(Arg::flag(|| ()).long("in").group("Input options")))
+                 .ends_with("\n\nInput options:\n  --in\n") );
+        assert!( usage(&Config::<()>::new("groups")).ends_with("\n\nOPTIONS:\n") );
+    }
+
+    #[test]
+    fn allow_negative_numbers() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Three, A, Num(f64) }
+
+        let config = Config::new("neg")
+            .arg(Arg::flag(|| Opt::Three).short('3'))
+            .arg(Arg::flag(|| Opt::A).short('a'))
+            .positional_parsed("NUM", Opt::Num);
+        assert_parse(&config, &["-3a"], &[Opt::Three, Opt::A]);
+        assert_parse_error_matches(&config, &["-3.5"], "option -.: unrecognized");
+
+        let config = config.allow_negative_numbers();
+        assert_parse(&config, &["-3.5", "-3", "-a", "-1e3"],
+                     &[Opt::Num(-3.5), Opt::Num(-3.0), Opt::A, Opt::Num(-1000.0)]);
+        assert_parse(&config, &["-3a", "-a3"], &[Opt::Three, Opt::A, Opt::A, Opt::Three]);
+        assert_parse_error_matches(&config, &["-3abc"], "option -b: unrecognized");
+        assert_parse_error_matches(&config, &["-inf"], "option -i: unrecognized");
+    }
+
+    #[test]
+    fn require_options_first() {
+        let pos = |s: &str| Pos::Positional(s.to_owned());
+
+        assert_parse(&pos_config(), &["file", "-a"], &[pos("file"), Pos::FlagA]);
+
+        let config = pos_config().require_options_first();
+        assert_parse(&config, &["-a", "file", "-a", "--"],
+                     &[Pos::FlagA, pos("file"), pos("-a"), pos("--")]);
+
+        assert_eq!( vec![Explanation::Positional("file".to_owned()),
+                         Explanation::Positional("-b".to_owned())],
+                    config.explain(vec!["file".to_owned(), "-b".to_owned()]) );
+
+        let config = config.resume_options_marker("++");
+        assert_parse(&config, &["file", "-a", "++", "-a", "x", "-a"],
+                     &[pos("file"), pos("-a"), Pos::FlagA, pos("x"), pos("-a")]);
+    }
+
+    #[test]
+    fn count_includes_each_clustered_option() {
+        let config = &fls_config();
+        let count = |args: &[&str]| config.iter(args.iter().map(ToString::to_string)).count();
+
+        assert_eq!( 3, count(&["-lsl"]) );
+        assert_eq!( 2, count(&["-lf", "2"]) );
+        assert_eq!( 3, count(&["-lf2", "-s"]) );
+        assert_eq!( 2, count(&["-lxs"]) );
+    }
+
+    #[test]
+    fn strict_value_consumption() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Out(String), Verbose }
+
+        let config = Config::new("strict")
+            .arg(Arg::parsed_param("FILE", Opt::Out).short('o').long("out"))
+            .arg(Arg::flag(|| Opt::Verbose).short('v').long("verbose"));
+        let out = |s: &str| Opt::Out(s.to_owned());
+
+        assert_parse(&config, &["--out", "--verbose"], &[out("--verbose")]);
+        assert_parse(&config, &["-o", "-v"], &[out("-v")]);
+
+        let config = config.strict_value_consumption(true);
+        let results: Vec<_> = config.iter(["--out", "--verbose"].iter().map(ToString::to_string))
+            .collect();
+        assert_eq!( vec![Err(Error::missing_param("--out")), Ok(Opt::Verbose)], results );
+        assert_parse_error_matches(&config, &["-o", "-v"],
+                                   "option -o: expected option parameter");
+        assert_parse(&config, &["--out=--verbose", "-o", "-x", "--out", "-"],
+                     &[out("--verbose"), out("-x"), out("-")]);
+    }
+
+    #[test]
+    fn plus_minus_toggles() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { X(bool), Y(bool), Z }
+
+        let config = Config::new("toggle")
+            .arg(Arg::toggle(Opt::X).short('x').long("ex"))
+            .arg(Arg::toggle(Opt::Y).short('y'))
+            .arg(Arg::flag(|| Opt::Z).short('z'));
+
+        assert_parse(&config, &["-x"], &[Opt::X(false)]);
+        assert_eq!( Err(Error::from_string("positional arguments not accepted: '+x'")
+                        .with_kind(ErrorKind::PositionalNotAccepted)),
+                    parse(&config, &["+x"]) );
+
+        let config = config.plus_minus_pairs(true);
+        assert_parse(&config, &["+x", "-x", "+xy", "-yxz", "--ex"],
+                     &[Opt::X(true), Opt::X(false), Opt::X(true), Opt::Y(true),
+                       Opt::Y(false), Opt::X(false), Opt::Z, Opt::X(true)]);
+        assert_eq!( Err(Error::from_string("not a toggle").with_option("+z")),
+                    parse(&config, &["+yz"]) );
+        assert_parse_error_matches(&config, &["+w"], "option +w: unrecognized");
+        assert!( usage(&config).contains("\n  -x, +x, --ex\n") );
+        assert_eq!( vec![Explanation::Plus('x'), Explanation::Short('y', None)],
+                    config.explain(vec!["+x".to_owned(), "-y".to_owned()]) );
+    }
+
+    #[test]
+    fn plus_options() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { PlusX, MinusX, Pos(String) }
+
+        let config = Config::new("plus")
+            .arg(Arg::toggle(|on| if on {Opt::PlusX} else {Opt::MinusX}).short('x'))
+            .arg(Arg::str_param("POS", |s| Ok(Opt::Pos(s.to_owned()))))
+            .plus_minus_pairs(true);
+
+        assert_parse(&config, &["+x", "-x"], &[Opt::PlusX, Opt::MinusX]);
+        assert_parse(&config, &["+", "-x"], &[Opt::Pos("+".to_owned()), Opt::MinusX]);
+    }
+
+    #[test]
+    fn parse_fold() {
+        #[derive(PartialEq, Debug, Default)]
+        struct Settings { freq: f32, volume: i32 }
+
+        let fold = |args: &[&str]| fls_config().parse_fold(
+            args.iter().map(ToString::to_string), Settings::default(),
+            |settings, opt| match opt {
+                FLS::Freq(freq) => settings.freq = freq,
+                FLS::Louder     => settings.volume += 1,
+                FLS::Softer     => settings.volume -= 1,
+            });
+
+        assert_eq!( Ok(Settings { freq: 2.5, volume: 1 }),
+                    fold(&["-ll", "--freq=2.5", "-s"]) );
+        assert!( fold(&["-l", "-x"]).is_err() );
+    }
+
+    #[test]
+    fn parse_or_message() {
+        let config = fls_config().bin_name("fls-bin");
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        assert_eq!( Ok(vec![FLS::Louder]), config.parse_or_message(args(&["-l"])) );
+        assert_eq!( Err("fls-bin: option -x: unrecognized\n\
+                         Try 'fls-bin --help' for more information.".to_owned()),
+                    config.parse_or_message(args(&["-x"])) );
+    }
+
+    #[test]
+    fn odd_inputs_do_not_panic() {
+        let config = &Config::new("odd")
+            .arg(Arg::str_param("V", |s| Ok(format!("é={}", s))).short('é').long("é"))
+            .arg(Arg::flag(|| "x".to_owned()).short('x').long("x"))
+            .arg(Arg::str_param("ARG", |s| Ok(s.to_owned())));
+        let inputs: &[&[&str]] = &[
+            &["-"], &["--"], &["--="], &["-=x"], &[""], &["-é"], &["-éü"], &["-xé"],
+            &["--é=ü"], &["--é="], &["--x=ü"], &["-\u{301}"], &["--\u{301}=\u{301}"],
+            &["=", "-="], &["---"], &["-x="], &["\u{0}"],
+        ];
+
+        for args in inputs {
+            let owned = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+            let _ = parse(config, args);
+            let _ = config.explain(owned);
+        }
+
+        assert_parse(config, &["-éü", "-xéa", "--é=ü"],
+                     &["é=ü".to_owned(), "x".to_owned(), "é=a".to_owned(),
+                       "é=ü".to_owned()]);
+        assert_parse_error_matches(config, &["--="], "option --=: empty option name");
+        assert_parse_error_matches(config, &["-="], "option -=: unrecognized");
+    }
+
+    #[test]
+    fn clear_on_empty() {
+        #[derive(PartialEq, Debug)]
+        enum Inc { Add(String), Clear }
+
+        let config = &Config::new("include")
+            .arg(Arg::parsed_param("DIR", Inc::Add).short('I').long("include")
+                 .with_clear_on_empty(|| Inc::Clear));
+        let add = |s: &str| Inc::Add(s.to_owned());
+
+        assert_parse(config, &["--include", "a", "--include=", "--include", "b"],
+                     &[add("a"), Inc::Clear, add("b")]);
+        assert_parse(config, &["-Ia", "-I", "", "-Ib"], &[add("a"), Inc::Clear, add("b")]);
+    }
+
+    #[test]
+    fn values_until_flag() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { File(String), Num(u8), Verbose }
+
+        let config = &Config::new("files")
+            .arg(Arg::parsed_param("FILE", Opt::File).short('f').long("files")
+                 .values_until_flag())
+            .arg(Arg::parsed_param("N", Opt::Num).short('n').values_until_flag())
+            .arg(Arg::flag(|| Opt::Verbose).short('v').long("verbose"))
+            .arg(Arg::parsed_param("REST", Opt::File));
+        let file = |s: &str| Opt::File(s.to_owned());
+
+        assert_parse(config, &["--files", "a", "b", "c", "--verbose", "d"],
+                     &[file("a"), file("b"), file("c"), Opt::Verbose, file("d")]);
+        assert_parse(config, &["-fa", "-", "-v", "--files=b", "--", "-c"],
+                     &[file("a"), file("-"), Opt::Verbose, file("b"), file("-c")]);
+        assert_parse(config, &["-n", "1", "2"], &[Opt::Num(1), Opt::Num(2)]);
+        assert_parse_error_matches(config, &["-n1", "x"],
+                                   "option -n: invalid value for <N:u8>: 'x'");
+        assert_parse_error_matches(config, &["-f", "a", "-x"], "option -x: unrecognized");
+    }
+
+    #[test]
+    fn to_command_forwards_positionals() {
+        let results = parse(&pos_config(), &["x", "-a", "--", "-v", "y"]).unwrap();
+        let rest: Vec<_> = results.into_iter()
+            .filter_map(|p| match p { Pos::Positional(s) => Some(s), _ => None })
+            .collect();
+
+        let command = to_command("grep", &rest);
+        assert_eq!( "grep", command.get_program() );
+        assert_eq!( vec!["x", "-v", "y"], command.get_args().collect::<Vec<_>>() );
+    }
+
+    #[test]
+    fn required_options() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Out(String), In(String), Verbose }
+
+        let config = &Config::new("required")
+            .arg(Arg::parsed_param("FILE", Opt::Out).short('o').long("output").required())
+            .arg(Arg::parsed_param("FILE", Opt::In).short('i').required())
+            .arg(Arg::flag(|| Opt::Verbose).short('v'));
+
+        assert_parse(config, &["-ix", "--output", "y"],
+                     &[Opt::In("x".to_owned()), Opt::Out("y".to_owned())]);
+        assert_parse_error_matches(config, &[], "option --output: required but not supplied");
+
+        let results: Vec<_> = config.iter(vec!["-v".to_owned()]).collect();
+        assert_eq!( vec![Ok(Opt::Verbose),
+                         Err(Error::from_string("required but not supplied")
+                             .with_option("--output")),
+                         Err(Error::from_string("required but not supplied")
+                             .with_option("-i"))],
+                    results );
+    }
+
+    #[test]
+    fn default_values() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Threads(usize), Verbose }
+
+        let config = &Config::new("threads")
+            .arg(Arg::parsed_param("N", Opt::Threads).short('j').long("threads")
+                 .default_value("4"))
+            .arg(Arg::flag(|| Opt::Verbose).short('v'));
+
+        assert_parse(config, &[], &[Opt::Threads(4)]);
+        assert_parse(config, &["-v"], &[Opt::Verbose, Opt::Threads(4)]);
+        assert_parse(config, &["-j2", "-v"], &[Opt::Threads(2), Opt::Verbose]);
+
+        let config = &Config::new("threads")
+            .arg(Arg::parsed_param("N", Opt::Threads).long("threads")
+                 .default_value("many").required());
+        assert_parse_error_matches(config, &[],
+                                   "option --threads: invalid value for <N:usize>: 'many'");
+    }
+
+    #[test]
+    fn counted_flag() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Verbosity(usize), Quiet }
+
+        let config = &Config::new("count")
+            .arg(Arg::counted(Opt::Verbosity).short('v').long("verbose"))
+            .arg(Arg::flag(|| Opt::Quiet).short('q'));
+
+        assert_parse(config, &["-vvv", "-v"], &[Opt::Verbosity(4)]);
+        assert_parse(config, &["-v", "-qv", "--verbose"], &[Opt::Quiet, Opt::Verbosity(3)]);
+        assert_parse(config, &["-q"], &[Opt::Quiet]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn iter_os_passes_raw_parameters() {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+        use std::path::PathBuf;
+
+        #[derive(PartialEq, Debug)]
+        enum Opt { Out(PathBuf), Name(String), Verbose, File(PathBuf) }
+
+        let config = Config::new("os")
+            .arg(Arg::os_param("PATH", |s| Ok(Opt::Out(PathBuf::from(s)))).short('o').long("out"))
+            .arg(Arg::parsed_param("NAME", Opt::Name).short('n'))
+            .arg(Arg::flag(|| Opt::Verbose).short('v'))
+            .arg(Arg::os_param("FILE", |s| Ok(Opt::File(PathBuf::from(s)))));
+        let bad = |prefix: &str| {
+            let mut bytes = prefix.as_bytes().to_vec();
+            bytes.extend_from_slice(b"f\xFFo");
+            OsString::from_vec(bytes)
+        };
+        let path = |prefix: &str| PathBuf::from(bad(prefix));
+        let os_parse = |args: Vec<OsString>| config.iter_os(args).collect::<Result<Vec<_>>>();
+
+        assert_eq!( Ok(vec![Opt::File(path("")), Opt::Out(path("")), Opt::Out(path("")),
+                            Opt::Verbose, Opt::Out(path("")), Opt::Out(path("x=")),
+                            Opt::Name("a".to_owned()), Opt::File(path("-"))]),
+                    os_parse(vec![bad(""), "-o".into(), bad(""), bad("-o"), bad("-vo"),
+                                  bad("--out=x="), "-na".into(), "--".into(), bad("-")]) );
+        assert_eq!( Ok(vec![Opt::Out(PathBuf::from("x")), Opt::Verbose]),
+                    os_parse(vec!["--out=x".into(), "-v".into()]) );
+        assert_eq!( Err(Error::from_string("invalid Unicode: f\u{FFFD}o").with_option("-nf\u{FFFD}o")),
+                    os_parse(vec![bad("-n")]) );
+    }
+
+    #[test]
+    fn iter_lenient_collects_errors() {
+        #[derive(PartialEq, Debug)]
+        struct Verbose;
+
+        let config = Config::new("lenient").arg(Arg::flag(|| Verbose).short('v'));
+        let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        let mut iter = config.iter_lenient(args(&["-x", "-v", "-y"]));
+        assert_eq!( vec![Verbose], iter.by_ref().collect::<Vec<_>>() );
+        assert_eq!( &[Error::unknown_flag("-x"), Error::unknown_flag("-y")], iter.errors() );
+
+        let mut iter = config.iter_lenient(args(&["-vxv", "-yv", "pos"]));
+        assert_eq!( 1, iter.by_ref().count() );
+        assert_eq!( 3, iter.errors().len() );
+    }
+
+    #[test]
+    fn max_tokens() {
+        use self::FLS::*;
+
+        let config = &fls_config().max_tokens(3);
+        assert_parse(config, &["-ls", "-f1"], &[Louder, Softer, Freq(1.0)]);
+
+        let args = ["-lsl", "-s", "-l"].iter().map(ToString::to_string);
+        let results: Vec<_> = config.iter(args).collect();
+        assert_eq!( vec![Ok(Louder), Ok(Softer), Ok(Louder),
+                         Err(Error::from_string("argument limit exceeded"))],
+                    results );
+        assert_parse_error_matches(config, &["-f", "1", "-l", "-s"], "argument limit exceeded");
+    }
+
+    #[test]
+    fn max_tokens_counts_options_without_results() {
+        let config = &Config::new("quiet")
+            .arg(Arg::counted(|n| n).short('v'))
+            .arg(Arg::flag(|| 0).short('q').ignored())
+            .max_tokens(2);
+        let limit = || Err(Error::from_string("argument limit exceeded"));
+        let many  = format!("-{}", "v".repeat(10_000));
+
+        assert_eq!( Ok(vec![2]), parse(config, &["-v", "-v"]) );
+        assert_eq!( Ok(vec![]), parse(config, &["-qq"]) );
+        assert_eq!( limit(), parse(config, &[&many]) );
+        assert_eq!( limit(), parse(config, &["-qqqqqqqq", "-q", "-q"]) );
+        assert_eq!( limit(), parse(config, &["-q", "--", "x"]) );
+    }
+
+    #[test]
+    fn iter_indexed_counts_each_option() {
+        let config = pos_config()
+            .arg(Arg::parsed_param("DIR", Pos::Positional).long("include"));
+        let args = ["--include", "a", "-a", "x", "--include=b", "-aa", "y"];
+        let result: Result<Vec<_>> =
+            config.iter_indexed(args.iter().map(ToString::to_string)).collect();
+
+        assert_eq!( Ok(vec![(0, Pos::Positional("a".to_owned())),
+                            (0, Pos::FlagA),
+                            (0, Pos::Positional("x".to_owned())),
+                            (1, Pos::Positional("b".to_owned())),
+                            (1, Pos::FlagA),
+                            (2, Pos::FlagA),
+                            (1, Pos::Positional("y".to_owned()))]),
+                    result );
+    }
+
+    #[test]
+    fn iter_indexed_end_results() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Inc(usize), Jobs(u32) }
+
+        let config = Config::new("indexed")
+            .arg(Arg::counted(Opt::Inc).long("inc"))
+            .arg(Arg::parsed_param("N", Opt::Jobs).long("jobs").default_value("4"));
+        let result = |args: &[&str]| config.iter_indexed(args.iter().map(ToString::to_string))
+            .collect::<Result<Vec<_>>>();
+
+        assert_eq!( Ok(vec![(3, Opt::Inc(3)), (0, Opt::Jobs(4))]),
+                    result(&["--inc", "--inc", "--inc"]) );
+        assert_eq!( Ok(vec![(0, Opt::Jobs(2)), (1, Opt::Inc(1))]),
+                    result(&["--jobs", "2", "--inc"]) );
+    }
+
     fn pos_config() -> Config<'static, Pos> {
         Config::new("pos")
             .arg(Arg::flag(|| Pos::FlagA).short('a'))
             .arg(Arg::parsed_param("POS", Pos::Positional))
     }
 
+    #[test]
+    fn cloned_iter_is_independent() {
+        let config = fls_config();
+        let args = vec!["-sl".to_owned(), "-s".to_owned()];
+        let mut iter = config.iter(args);
+
+        assert_eq!( Some(Ok(FLS::Softer)), iter.next() );
+
+        let mut clone = iter.clone();
+        assert_eq!( Some(Ok(FLS::Louder)), clone.next() );
+        assert_eq!( Some(Ok(FLS::Softer)), clone.next() );
+        assert_eq!( None, clone.next() );
+
+        assert_eq!( Some(Ok(FLS::Louder)), iter.next() );
+        assert_eq!( Some(Ok(FLS::Softer)), iter.next() );
+        assert_eq!( None, iter.next() );
+    }
+
+    #[test]
+    fn checkpoint_and_restore() {
+        let config = fls_config();
+        let args = vec!["-ls".to_owned(), "--freq=2".to_owned()];
+        let mut iter = config.iter(args);
+
+        assert_eq!( Some(Ok(FLS::Louder)), iter.next() );
+        let checkpoint = iter.checkpoint();
+        assert_eq!( Some(Ok(FLS::Softer)), iter.next() );
+        assert_eq!( Some(Ok(FLS::Freq(2.0))), iter.next() );
+
+        iter.restore(checkpoint);
+        assert_eq!( Some(Ok(FLS::Softer)), iter.next() );
+        assert_eq!( Some(Ok(FLS::Freq(2.0))), iter.next() );
+        assert_eq!( None, iter.next() );
+    }
+
+    #[test]
+    fn peek() {
+        let config = fls_config();
+        let args = vec!["-l".to_owned(), "-x".to_owned()];
+        let mut iter = config.iter(args);
+
+        assert_eq!( Some(&Ok(FLS::Louder)), iter.peek() );
+        assert_eq!( Some(&Ok(FLS::Louder)), iter.peek() );
+        assert_eq!( Some(Ok(FLS::Louder)), iter.next() );
+        assert!( iter.peek().unwrap().is_err() );
+        assert!( iter.next().unwrap().is_err() );
+        assert_eq!( None, iter.peek() );
+        assert_eq!( None, iter.peek() );
+        assert_eq!( None, iter.next() );
+    }
+
+    #[test]
+    fn checkpoint_after_peek() {
+        let config = fls_config();
+        let args = vec!["-sf".to_owned(), "2".to_owned(), "-l".to_owned()];
+        let mut iter = config.iter(args);
+
+        assert_eq!( Some(Ok(FLS::Softer)), iter.next() );
+        assert_eq!( Some(&Ok(FLS::Freq(2.0))), iter.peek() );
+        let checkpoint = iter.checkpoint();
+        assert_eq!( Some(Ok(FLS::Freq(2.0))), iter.next() );
+        assert_eq!( Some(Ok(FLS::Louder)), iter.next() );
+        assert_eq!( None, iter.next() );
+
+        iter.restore(checkpoint);
+        assert_eq!( Some(&Ok(FLS::Freq(2.0))), iter.peek() );
+        let mut clone = iter.clone();
+        assert_eq!( Some(Ok(FLS::Freq(2.0))), iter.next() );
+        assert_eq!( Some(Ok(FLS::Louder)), iter.next() );
+        assert_eq!( None, iter.next() );
+        assert_eq!( Some(Ok(FLS::Freq(2.0))), clone.next() );
+        assert_eq!( Some(&Ok(FLS::Louder)), clone.peek() );
+        assert_eq!( Some(Ok(FLS::Louder)), clone.clone().next() );
+    }
+
+    fn env_config(unset_is_error: bool) -> Config<'static, String> {
+        let lookup = |name: &str| if name == "HOME" {Some("/home/me".to_owned())} else {None};
+        Config::new("env")
+            .arg(Arg::str_param("PATH", |s| Ok(s.to_owned()))
+                 .long("path")
+                 .expand_env_with(unset_is_error, lookup))
+    }
+
+    #[test]
+    fn expand_env_in_param() {
+        let config = &env_config(true);
+        assert_parse(config, &["--path", "${HOME}/x"], &["/home/me/x".to_owned()]);
+        assert_parse(config, &["--path=$HOME/x"], &["/home/me/x".to_owned()]);
+        assert_parse(config, &["--path", "a$$b"], &["a$b".to_owned()]);
+        assert_parse(config, &["--path", "$"], &["$".to_owned()]);
+    }
+
+    #[test]
+    fn expand_env_unset_var() {
+        assert_parse_error_matches(&env_config(true), &["--path", "${NOPE}/x"],
+                                   "environment variable not set: NOPE");
+        assert_parse(&env_config(false), &["--path", "${NOPE}/x"], &["/x".to_owned()]);
+    }
+
+    #[derive(PartialEq, Debug)]
+    enum Compress {
+        Default,
+        Level(u8),
+        File(String),
+    }
+
+    fn compress_config() -> Config<'static, Compress> {
+        Config::new("compress")
+            .arg(Arg::optional_param("LEVEL",
+                                     || Compress::Default,
+                                     |s| s.parse().map(Compress::Level)
+                                          .map_err(|e| Error::from_string(&e)))
+                 .short('c').long("compress"))
+            .arg(Arg::parsed_param("FILE", Compress::File))
+    }
+
+    #[test]
+    fn optional_param_bare_and_valued() {
+        let config = &compress_config();
+        assert_parse(config, &["--compress"], &[Compress::Default]);
+        assert_parse(config, &["--compress=9"], &[Compress::Level(9)]);
+        assert_parse(config, &["-c"], &[Compress::Default]);
+        assert_parse(config, &["-c9"], &[Compress::Level(9)]);
+        assert_parse(config, &["-c", "9"],
+                     &[Compress::Default, Compress::File("9".to_owned())]);
+        assert_parse_error(config, &["--compress=high"]);
+    }
+
+    #[test]
+    fn quick_parse_spec() {
+        assert_eq!( Ok(vec![('a', None),
+                            ('b', Some("X".to_owned())),
+                            ('o', Some("Y".to_owned()))]),
+                    quick_parse("ab:o:", &["-a", "-bX", "-o", "Y"]) );
+        assert_eq!( Ok(vec![('a', None), ('b', Some("a".to_owned()))]),
+                    quick_parse("ab:o:", &["-aba"]) );
+        assert!( quick_parse("ab:o:", &["-o"]).is_err() );
+        assert!( quick_parse("ab:o:", &["file"]).is_err() );
+    }
+
+    #[test]
+    fn quick_parse_bad_spec() {
+        assert!( quick_parse(":a", &[]).is_err() );
+        assert!( quick_parse("a::", &[]).is_err() );
+        assert!( quick_parse("aba", &[]).is_err() );
+    }
+
+    #[test]
+    fn parse_shorts_flags_and_positionals() {
+        let spec = ['a', 'b', 'c', 'd', 'e'];
+        assert_eq!( Ok((0b1111, vec!["x"])),
+                    parse_shorts(&spec, &["-abc", "-d", "x"]) );
+        assert_eq!( Ok((0b10000, vec!["-", "-a"])),
+                    parse_shorts(&spec, &["-", "-e", "--", "-a"]) );
+        assert_eq!( Err("option -z: unrecognized".to_owned()),
+                    parse_shorts(&spec, &["-az"]).map_err(|e| e.to_string()) );
+        assert!( parse_shorts(&spec, &["--all"]).is_err() );
+    }
+
+    #[test]
+    fn cursor_manual_parse() {
+        let config = fls_config();
+        let args = vec!["-sf".to_owned(), "2".to_owned(), "--louder".to_owned(),
+                        "-x".to_owned()];
+        let mut cursor = config.cursor(args);
+
+        let arg = cursor.next_raw().unwrap().to_owned();
+        assert_eq!( ArgState::ShortOption('s', "f"), cursor.classify(&arg) );
+        assert_eq!( Ok(FLS::Softer), cursor.parse_known(&arg) );
+
+        let arg = cursor.next_raw().unwrap().to_owned();
+        assert_eq!( "-f", arg );
+        assert_eq!( Ok(FLS::Freq(2.0)), cursor.parse_known(&arg) );
+
+        let arg = cursor.next_raw().unwrap().to_owned();
+        assert_eq!( ArgState::LongOption("louder", None), cursor.classify(&arg) );
+        assert_eq!( Ok(FLS::Louder), cursor.parse_known(&arg) );
+
+        let arg = cursor.next_raw().unwrap().to_owned();
+        assert_eq!( Err(Error::unknown_flag("-x")), cursor.parse_known(&arg) );
+
+        assert_eq!( None, cursor.next_raw() );
+    }
+
+    fn usage<T>(config: &Config<T>) -> String {
+        config.usage_string()
+    }
+
+    #[test]
+    fn usage_and_version_strings() {
+        let config = fls_config().version("1.2.3");
+        let mut buf = Vec::new();
+        config.write_usage(&mut buf).unwrap();
+
+        assert_eq!( buf, config.usage_string().into_bytes() );
+        assert_eq!( "fls 1.2.3\n", config.version_string() );
+    }
+
+    #[test]
+    fn optional_param_usage() {
+        let config = Config::<()>::new("color")
+            .arg(Arg::optional_param("WHEN", || (), |_| Ok(())).short('c').long("color"))
+            .arg(Arg::optional_param("N", || (), |_| Ok(())).short('n'))
+            .arg(Arg::str_param("FILE", |_| Ok(())).short('o'));
+        let usage = usage(&config);
+
+        assert!( usage.contains("  -c, --color[=WHEN]\n"), "{}", usage );
+        assert!( usage.contains("  -n[N]\n"), "{}", usage );
+        assert!( usage.contains("  -o <FILE>\n"), "{}", usage );
+    }
+
+    #[test]
+    fn bin_name_in_usage_line() {
+        let config = Config::<()>::new("busybox").version("1.0");
+        assert!( usage(&config).starts_with("busybox 1.0\n\nUsage: busybox OPTION...\n") );
+
+        let config = config.bin_name("ls");
+        assert!( usage(&config).starts_with("busybox 1.0\n\nUsage: ls OPTION...\n") );
+    }
+
+    #[test]
+    fn positional_args_section() {
+        let config = Config::new("cp")
+            .arg(Arg::flag(|| Pos::FlagA).short('a').description("Archive"))
+            .arg(Arg::parsed_param("FILE", Pos::Positional).description("Files to copy"));
+        assert!( usage(&config).ends_with("Usage: cp OPTION... [--] FILE...\n\
+                                           \nARGS:\n  FILE   Files to copy\n\
+                                           \nOPTIONS:\n  -a   Archive\n") );
+
+        let config = Config::<()>::new("cp");
+        assert!( !usage(&config).contains("ARGS:") );
+    }
+
+    #[test]
+    fn short_aliases() {
+        #[derive(PartialEq, Debug)]
+        struct Help;
+
+        let config = &Config::new("help")
+            .arg(Arg::flag(|| Help).short('h').short_aliases(&['?', 'H']).long("help"));
+        assert_parse(config, &["-h", "-?", "-H?"], &[Help, Help, Help, Help]);
+        assert!( usage(config).contains("\n  -h, --help\n") );
+
+        let mut config = Config::new("help");
+        config.arg_safe(Arg::flag(|| Help).short('h')).unwrap();
+        assert_eq!( Err(Error::from_string("repeated in config").with_option("-h")),
+                    config.arg_safe(Arg::flag(|| Help).short('?').short_aliases(&['h'])) );
+        assert_eq!( Err(Error::from_string("repeated in config").with_option("-x")),
+                    config.arg_safe(Arg::flag(|| Help).short('x').short_aliases(&['x'])) );
+        config.arg_safe(Arg::flag(|| Help).short('?')).unwrap();
+        assert_parse(&config, &["-?"], &[Help]);
+    }
+
+    #[test]
+    fn short_alias() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Help, Quiet }
+
+        let config = &Config::new("help")
+            .arg(Arg::flag(|| Opt::Help).short('h').short_alias('?'))
+            .arg(Arg::flag(|| Opt::Quiet).short('q'));
+        assert_parse(config, &["-?", "-h", "-q?"], &[Opt::Help, Opt::Help, Opt::Quiet, Opt::Help]);
+
+        let mut config = Config::new("help");
+        config.arg_safe(Arg::flag(|| Opt::Quiet).short('?')).unwrap();
+        assert_eq!( Err(Error::from_string("repeated in config").with_option("-?")),
+                    config.arg_safe(Arg::flag(|| Opt::Help).short('h').short_alias('?')) );
+    }
+
+    #[test]
+    fn long_aliases() {
+        #[derive(PartialEq, Debug)]
+        struct Color;
+
+        let config = &Config::new("color")
+            .arg(Arg::flag(|| Color).short('c').long("color").alias("colour"))
+            .arg(Arg::flag(|| Color).long("grey").aliases(&["gray", "gris"]));
+        assert_parse(config, &["--color", "--colour", "-c", "--gray", "--gris", "--grey"],
+                     &[Color, Color, Color, Color, Color, Color]);
+        assert!( usage(config).contains("\n  -c, --color, --colour\n") );
+        assert!( usage(config).contains("\n  --grey, --gray, --gris\n") );
+
+        let mut config = Config::new("color");
+        config.arg_safe(Arg::flag(|| Color).long("color")).unwrap();
+        assert_eq!( Err(Error::from_string("repeated in config").with_option("--color")),
+                    config.arg_safe(Arg::flag(|| Color).long("colour").alias("color")) );
+        assert_eq!( Err(Error::from_string("repeated in config").with_option("--hue")),
+                    config.arg_safe(Arg::flag(|| Color).long("hue").alias("hue")) );
+        config.arg_safe(Arg::flag(|| Color).long("colour")).unwrap();
+    }
+
+    #[test]
+    fn help_goes_to_chosen_stream() {
+        let config = fls_config();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+
+        config.write_help(HelpStream::Stdout, &mut out, &mut err, false).unwrap();
+        assert_eq!( usage(&config).into_bytes(), out );
+        assert!( err.is_empty() );
+
+        out.clear();
+        config.write_help(HelpStream::Stderr, &mut out, &mut err, false).unwrap();
+        assert!( out.is_empty() );
+        assert_eq!( usage(&config).into_bytes(), err );
+    }
+
+    #[test]
+    fn auto_help_prints_usage_and_exits() {
+        let config = fls_config().auto_help().unwrap();
+
+        if env::var_os("FOROPTS_TEST_AUTO_HELP").is_some() {
+            let _ = parse(&config, &["-l", "--help", "-s"]);
+            panic!("--help did not exit");
+        }
+
+        let output = Command::new(env::current_exe().unwrap())
+            .args(["--exact", "tests::auto_help_prints_usage_and_exits", "--nocapture"])
+            .env("FOROPTS_TEST_AUTO_HELP", "1")
+            .output().unwrap();
+        assert!( output.status.success() );
+        assert!( String::from_utf8_lossy(&output.stdout).contains(&usage(&config)) );
+        assert!( usage(&config).contains("\n  -h, --help   Print help information\n") );
+
+        assert_eq!( Err(Error::from_string("repeated in config").with_option("-h")),
+                    Config::new("h").arg(Arg::flag(|| ()).short('h')).auto_help().map(|_| ()) );
+    }
+
+    #[test]
+    fn cursor_auto_help_prints_usage_and_exits() {
+        let config = fls_config().auto_help().unwrap();
+
+        if env::var_os("FOROPTS_TEST_CURSOR_HELP").is_some() {
+            let mut cursor = config.cursor(vec!["-sh".to_owned()]);
+            let arg = cursor.next_raw().unwrap().to_owned();
+            assert_eq!( Ok(FLS::Softer), cursor.parse_known(&arg) );
+            let arg = cursor.next_raw().unwrap().to_owned();
+            let _ = cursor.parse_known(&arg);
+            panic!("-h did not exit");
+        }
+
+        let output = Command::new(env::current_exe().unwrap())
+            .args(["--exact", "tests::cursor_auto_help_prints_usage_and_exits", "--nocapture"])
+            .env("FOROPTS_TEST_CURSOR_HELP", "1")
+            .output().unwrap();
+        assert!( output.status.success() );
+        assert!( String::from_utf8_lossy(&output.stdout).contains(&usage(&config)) );
+    }
+
+    #[test]
+    fn auto_version_prints_version_and_exits() {
+        let config = fls_config().version("1.2.3").auto_version().unwrap();
+
+        if env::var_os("FOROPTS_TEST_AUTO_VERSION").is_some() {
+            let _ = parse(&config, &["-sV"]);
+            panic!("-V did not exit");
+        }
+
+        let output = Command::new(env::current_exe().unwrap())
+            .args(["--exact", "tests::auto_version_prints_version_and_exits", "--nocapture"])
+            .env("FOROPTS_TEST_AUTO_VERSION", "1")
+            .output().unwrap();
+        assert!( output.status.success() );
+        assert!( String::from_utf8_lossy(&output.stdout).contains("fls 1.2.3\n") );
+
+        assert_eq!( Err(Error::from_string("no version set").with_option("--version")),
+                    fls_config().auto_version().map(|_| ()) );
+        assert_eq!( Err(Error::from_string("repeated in config").with_option("--version")),
+                    Config::new("v").version("1")
+                        .arg(Arg::flag(|| ()).long("version")).auto_version().map(|_| ()) );
+    }
+
+    #[test]
+    fn parse_or_help_error_goes_to_stderr() {
+        let config = fls_config();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+
+        let args = vec!["-s".to_owned()];
+        assert_eq!( Ok(vec![FLS::Softer]), config.parse_or_help(args, &mut out, &mut err, false) );
+        assert!( out.is_empty() && err.is_empty() );
+
+        let args = vec!["-x".to_owned()];
+        assert_eq!( Err(1), config.parse_or_help(args, &mut out, &mut err, false) );
+        assert!( out.is_empty() );
+        let err = String::from_utf8(err).unwrap();
+        assert!( err.starts_with("Syntax error: option -x: unrecognized\n") );
+        assert!( err.ends_with(&usage(&config)) );
+    }
+
+    #[test]
+    fn key_value_param() {
+        let config = &Config::new("cc")
+            .arg(Arg::key_value("KEY=VALUE", '=', |k, v| (k, v)).short('D'));
+        let pair = |k: &str, v: &str| (k.to_owned(), v.to_owned());
+
+        assert_parse(config, &["-Dfoo=bar"], &[pair("foo", "bar")]);
+        assert_parse(config, &["-D", "foo=bar=baz", "-Dx="],
+                     &[pair("foo", "bar=baz"), pair("x", "")]);
+        assert_parse_error_matches(config, &["-D", "foo"], "option -D: expected KEY=VALUE");
+    }
+
+    #[test]
+    fn long_abbreviations() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Verbose, Version, Verb, Color }
+
+        let config = Config::new("abbrev")
+            .arg(Arg::flag(|| Opt::Verbose).long("verbose"))
+            .arg(Arg::flag(|| Opt::Version).long("version"))
+            .arg(Arg::flag(|| Opt::Color).long("color").alias("colour"));
+        assert_parse_error_matches(&config, &["--verb"], "option --verb: unrecognized");
+
+        let config = config.allow_abbreviations();
+        assert_parse(&config, &["--verb", "--vers", "--c", "--colo", "--version"],
+                     &[Opt::Verbose, Opt::Version, Opt::Color, Opt::Color, Opt::Version]);
+
+        let error = parse(&config, &["--ver"]).unwrap_err();
+        assert_eq!( "option --ver: ambiguous (verbose, version)", error.to_string() );
+        assert_eq!( Some(&["verbose".to_owned(), "version".to_owned()][..]),
+                    error.ambiguous_candidates() );
+        assert_parse_error_matches(&config, &["--verbosity"], "option --verbosity: unrecognized");
+
+        let config = config.arg(Arg::flag(|| Opt::Verb).long("verb"));
+        assert_parse(&config, &["--verb", "--verbo"], &[Opt::Verb, Opt::Verbose]);
+        assert_eq!( Ok(vec!["--verbose".to_owned()]),
+                    config.canonicalize(vec!["--verbo".to_owned()]) );
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn normalize_unicode() {
+        use super::Form;
+
+        let config = || Config::new("norm")
+            .arg(Arg::flag(|| 1).long("caf\u{e9}").short('\u{e9}'))
+            .arg(Arg::flag(|| 2).long("file").short('A'));
+
+        assert_parse_error_matches(&config(), &["--cafe\u{301}"], "unrecognized");
+
+        let nfc = config().normalize_unicode(Form::Nfc);
+        assert_parse(&nfc, &["--cafe\u{301}", "--caf\u{e9}", "-\u{e9}"], &[1, 1, 1]);
+        assert_parse_error_matches(&nfc, &["--\u{fb01}le"], "unrecognized");
+
+        let nfkc = config().normalize_unicode(Form::Nfkc);
+        assert_parse(&nfkc, &["--\u{fb01}le", "-\u{ff21}", "--cafe\u{301}"], &[2, 2, 1]);
+    }
+
+    #[test]
+    fn case_insensitive_long() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Verbose, Out(String) }
+
+        let config = Config::new("case")
+            .arg(Arg::flag(|| Opt::Verbose).long("verbose"))
+            .arg(Arg::parsed_param("FILE", Opt::Out).long("OutFile"));
+        assert_parse_error_matches(&config, &["--Verbose"], "option --Verbose: unrecognized");
+
+        let config = config.case_insensitive_long(true);
+        assert_parse(&config, &["--Verbose", "--VERBOSE", "--outfile=x"],
+                     &[Opt::Verbose, Opt::Verbose, Opt::Out("x".to_owned())]);
+        assert!( usage(&config).contains("\n  --OutFile <FILE>\n") );
+
+        let mut config = config.allow_abbreviations();
+        assert_parse(&config, &["--VERB", "--out", "y"], &[Opt::Verbose, Opt::Out("y".to_owned())]);
+        assert_eq!( Err(Error::from_string("repeated in config").with_option("--VERBOSE")),
+                    config.arg_safe(Arg::flag(|| Opt::Verbose).long("VERBOSE")) );
+    }
+
+    #[test]
+    fn case_insensitive_long_turned_off() {
+        let config = &Config::new("case")
+            .arg(Arg::flag(|| 1).long("Verbose").alias("Loud"))
+            .arg(Arg::flag(|| 2).long("quiet"))
+            .case_insensitive_long(true)
+            .case_insensitive_long(false);
+        assert_parse(config, &["--Verbose", "--Loud", "--quiet"], &[1, 1, 2]);
+        assert_parse_error_matches(config, &["--verbose"], "option --verbose: unrecognized");
+    }
+
+    #[test]
+    fn single_dash_long() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Version, Verbose, Out(String), E }
+
+        let config = Config::new("dash")
+            .arg(Arg::flag(|| Opt::Version).long("version"))
+            .arg(Arg::parsed_param("FILE", Opt::Out).long("out"))
+            .arg(Arg::flag(|| Opt::E).short('e').long("extra"));
+        assert_parse_error_matches(&config, &["-version"], "option -v: unrecognized");
+
+        let config = config.allow_single_dash_long();
+        assert_parse(&config, &["-version", "-out=x", "-out", "y", "--version"],
+                     &[Opt::Version, Opt::Out("x".to_owned()), Opt::Out("y".to_owned()),
+                       Opt::Version]);
+        assert_parse_error_matches(&config, &["-extra"], "option -x: unrecognized");
+        assert_parse_error_matches(&config, &["-verbose"], "option -v: unrecognized");
+
+        let config = config.arg(Arg::flag(|| Opt::Verbose).short('v'));
+        assert_parse_error_matches(&config, &["-version"], "option -r: unrecognized");
+        assert_parse_error_matches(&config, &["-vout=x"], "option -o: unrecognized");
+    }
+
+    #[test]
+    fn single_dash_long_in_cluster_rest() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { A, Abc, Bc }
+
+        let config = Config::new("dash")
+            .arg(Arg::flag(|| Opt::A).short('a'))
+            .arg(Arg::flag(|| Opt::Abc).long("abc"))
+            .arg(Arg::flag(|| Opt::Bc).long("bc"))
+            .allow_single_dash_long();
+        let args = || vec!["-abc".to_owned(), "-bc".to_owned()];
+
+        assert_eq!( vec![Ok(Opt::A), Err(Error::unknown_flag("-b")), Ok(Opt::Bc)],
+                    config.iter(args()).collect::<Vec<_>>() );
+        assert_eq!( vec![Explanation::Short('a', None),
+                         Explanation::Error(Error::unknown_flag("-b")),
+                         Explanation::Long("bc".to_owned(), None)],
+                    config.explain(args()) );
+
+        let mut cursor = config.cursor(args());
+        let arg = cursor.next_raw().unwrap().to_owned();
+        assert_eq!( Ok(Opt::A), cursor.parse_known(&arg) );
+        let arg = cursor.next_raw().unwrap().to_owned();
+        assert_eq!( "-bc", arg );
+        assert_eq!( ArgState::ShortOption('b', "c"), cursor.classify(&arg) );
+        assert_eq!( Err(Error::unknown_flag("-b")), cursor.parse_known(&arg) );
+        let arg = cursor.next_raw().unwrap().to_owned();
+        assert_eq!( Ok(Opt::Bc), cursor.parse_known(&arg) );
+    }
+
+    #[test]
+    fn ambiguous_error_lists_candidates() {
+        let error = Error::ambiguous(vec!["verbose", "version"]).with_option("--ver");
+        assert_eq!( Some(&["verbose".to_owned(), "version".to_owned()][..]),
+                    error.ambiguous_candidates() );
+        assert_eq!( "option --ver: ambiguous (verbose, version)", error.to_string() );
+        assert_eq!( None, Error::from_string("unrecognized").ambiguous_candidates() );
+    }
+
+    #[test]
+    fn canonicalize() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { A, B, Out(String), Level(String), File(String) }
+
+        let config = Config::new("canon")
+            .arg(Arg::flag(|| Opt::A).short('a').long("all"))
+            .arg(Arg::flag(|| Opt::B).short('b'))
+            .arg(Arg::parsed_param("FILE", Opt::Out).short('o').short_aliases(&['O']).long("out"))
+            .arg(Arg::optional_param("N", || Opt::Level(String::new()), |s| Ok(Opt::Level(s.to_owned())))
+                 .short('l'))
+            .arg(Arg::parsed_param("FILE", Opt::File));
+        let canon = |args: &[&str]| config.canonicalize(args.iter().map(ToString::to_string));
+        let strings = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        assert_eq!( Ok(strings(&["--all", "-b", "--out=x", "--", "file"])),
+                    canon(&["-ab", "--out=x", "file"]) );
+        assert_eq!( canon(&["-ab", "--out=x", "file"]),
+                    canon(&["--all", "-b", "file", "-Ox"]) );
+        assert_eq!( Ok(strings(&["--out=y", "-l", "-l3", "-b", "--", "-a", "z"])),
+                    canon(&["-Oy", "-l", "-l3", "-b", "--", "-a", "z"]) );
+        assert_eq!( Ok(vec![]), canon(&[]) );
+        assert_eq!( Err(Error::unknown_flag("-x")), canon(&["-ax"]) );
+    }
+
+    #[test]
+    fn canonicalize_keeps_meaning() {
+        let config = || pos_config()
+            .arg(Arg::str_param("OUT", |_| Ok(Pos::FlagA)).short('o').long("out"))
+            .arg(Arg::flag(|| Pos::FlagA).long("verbose"))
+            .arg(Arg::str_param("IN", |_| Ok(Pos::FlagA)).short('i'));
+        let strings = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        let strict = config().strict_value_consumption(true);
+        assert_eq!( Err(Error::missing_param("--out")),
+                    strict.canonicalize(strings(&["--out", "--verbose"])) );
+        assert_eq!( Ok(strings(&["-i-a", "-i", "x"])),
+                    strict.canonicalize(strings(&["-i-a", "-ix"])) );
+
+        let last = config().value_short_must_be_last(true);
+        assert!( last.canonicalize(strings(&["-oa"])).is_err() );
+        assert!( last.canonicalize(strings(&["-ia"])).is_err() );
+        assert_eq!( Ok(strings(&["--out=a", "-a"])), last.canonicalize(strings(&["-o", "a", "-a"])) );
+
+        let first = config().require_options_first().resume_options_marker("++");
+        assert_eq!( Ok(strings(&["-a", "--", "f"])),
+                    first.canonicalize(strings(&["f", "++", "-a"])) );
+        assert_eq!( Err(Error::from_string(
+                        "arguments have no canonical form that parses the same way")),
+                    first.canonicalize(strings(&["++"])) );
+
+        let stdin = config().args_from_stdin(true).stdin_reader(io::Cursor::new("-a"));
+        assert!( stdin.canonicalize(strings(&["-"])).is_err() );
+        assert_eq!( Ok(strings(&["--", "-"])), stdin.canonicalize(strings(&["--", "-"])) );
+    }
+
+    #[test]
+    fn canonicalize_round_trip() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { X(bool), File(String), Verbose, Pos(String) }
+
+        let config = Config::new("round")
+            .arg(Arg::toggle(Opt::X).short('x').long("ex"))
+            .arg(Arg::parsed_param("FILE", Opt::File).short('f').long("files")
+                 .values_until_flag())
+            .arg(Arg::flag(|| Opt::Verbose).short('v').long("verbose"))
+            .arg(Arg::parsed_param("POS", Opt::Pos))
+            .plus_minus_pairs(true);
+        let strings = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        for args in &[&["-x", "+x", "--ex", "-vx"][..],
+                      &["--files", "a", "b", "-v", "c"][..],
+                      &["-fa", "b", "--", "c", "-x"][..],
+                      &["-xf", "a", "b", "--files=c", "d", "-x"][..]] {
+            let canonical = config.canonicalize(strings(args)).unwrap();
+            assert_eq!( parse(&config, args),
+                        config.iter(canonical.clone()).collect::<Result<Vec<_>>>(),
+                        "{:?} canonicalized to {:?}", args, canonical );
+        }
+
+        assert_eq!( Ok(strings(&["-x", "+x", "--ex", "--files=a", "--files=b", "--verbose",
+                                 "--", "c"])),
+                    config.canonicalize(strings(&["-x", "+x", "--ex", "-fa", "b", "-v", "c"])) );
+    }
+
+    #[test]
+    fn completion_model() {
+        let config = pos_config()
+            .bin_name("pos-bin")
+            .arg(Arg::optional_param("WHEN", || Pos::FlagA, |_| Ok(Pos::FlagA))
+                 .long("color").alias("colour").description("colorize output")
+                 .possible_values(&["auto", "always", "never"]));
+
+        assert_eq!( CompletionModel {
+                        bin_name:       "pos-bin".to_owned(),
+                        options:        vec![
+                            OptionCompletion {
+                                shorts:         vec!['a'],
+                                long:           None,
+                                long_aliases:   vec![],
+                                value_name:     None,
+                                value_optional: false,
+                                possible_values: vec![],
+                                description:    String::new(),
+                            },
+                            OptionCompletion {
+                                shorts:         vec![],
+                                long:           Some("color".to_owned()),
+                                long_aliases:   vec!["colour".to_owned()],
+                                value_name:     Some("WHEN".to_owned()),
+                                value_optional: true,
+                                possible_values: vec!["auto".to_owned(), "always".to_owned(),
+                                                      "never".to_owned()],
+                                description:    "colorize output".to_owned(),
+                            },
+                        ],
+                        positionals:    vec![
+                            PositionalCompletion {
+                                name:           "POS".to_owned(),
+                                description:    String::new(),
+                            },
+                        ],
+                    },
+                    config.completion_model() );
+    }
+
+    #[test]
+    fn arg_from_spec() {
+        let mut config = Config::new("spec");
+        config.arg_from_spec("-o, --output <FILE>", |s| Ok(format!("o={}", s))).unwrap();
+        config.arg_from_spec("--verbose", |_| Ok("verbose".to_owned())).unwrap();
+        config.arg_from_spec("-n <N>", |s| Ok(format!("n={}", s))).unwrap();
+        config.arg_from_spec("<LEVEL> -l", |s| Ok(format!("l={}", s))).unwrap();
+
+        assert_parse(&config, &["-ofile", "--output", "x", "--verbose", "-n1", "-l", "2"],
+                     &["o=file", "o=x", "verbose", "n=1", "l=2"]
+                         .iter().map(ToString::to_string).collect::<Vec<_>>());
+        assert!( usage(&config).contains("\n  -o, --output <FILE>\n") );
+
+        for spec in &["", "<FILE>", "-ab", "-o -p", "--", "---", "o", "-o <A> <B>"] {
+            assert_eq!( Err(Error::from_string(&format!("invalid option spec: {:?}", spec))),
+                        config.arg_from_spec(spec, |s| Ok(s.to_owned())) );
+        }
+        assert_eq!( Err(Error::from_string("repeated in config").with_option("--verbose")),
+                    config.arg_from_spec("--verbose", |s| Ok(s.to_owned())) );
+    }
+
+    #[test]
+    fn struct_parser() {
+        #[derive(Default, Debug, PartialEq)]
+        struct Opts {
+            verbose:    bool,
+            level:      u8,
+            out:        String,
+        }
+
+        let parser = StructParser::new("struct")
+            .flag("verbose", |s: &mut Opts, v| s.verbose = v)
+            .value("level", |s: &mut Opts, v| s.level = v)
+            .value("out", |s: &mut Opts, v| s.out = v);
+        let run = |args: &[&str]| parser.run(args.iter().map(ToString::to_string));
+
+        assert_eq!( Ok(Opts { verbose: true, level: 3, out: "b".to_owned() }),
+                    run(&["--out=a", "--level", "3", "--verbose", "--out", "b"]) );
+        assert_eq!( Ok(Opts::default()), run(&[]) );
+        assert!( run(&["--level=x"]).is_err() );
+    }
+
+    #[test]
+    fn value_predicate() {
+        let config = Config::new("level")
+            .arg(Arg::parsed_param("LEVEL", |n: u8| n).short('l')
+                 .value_predicate(|s| s.len() == 1, "must be a single digit"));
+
+        assert_parse(&config, &["-l", "7"], &[7]);
+        assert_eq!( Err(Error::from_string("must be a single digit").with_option("-l10")),
+                    parse(&config, &["-l10"]) );
+        assert_parse_error_matches(&config, &["-lx"],
+                                   "option -lx: invalid value for <LEVEL:u8>: 'x'");
+    }
+
+    #[test]
+    fn possible_values() {
+        let config = Config::new("color")
+            .arg(Arg::str_param("WHEN", |s| Ok(s.to_owned())).long("color")
+                 .possible_values(&["auto", "always", "never"]));
+
+        assert_parse(&config, &["--color=always", "--color", "never"],
+                     &["always".to_owned(), "never".to_owned()]);
+        assert_eq!( Err(Error::from_string("'sometimes' is not one of auto, always, never")
+                        .with_option("--color")),
+                    parse(&config, &["--color", "sometimes"]) );
+        assert_parse_error_matches(&config, &["--color=sometimes"],
+                                   "'sometimes' is not one of auto, always, never");
+
+        let config = Config::new("level")
+            .arg(Arg::parsed_param("LEVEL", |n: u8| n).short('l')
+                 .possible_values(&["1", "2", "300"]));
+        assert_parse(&config, &["-l2"], &[2]);
+        assert_parse_error_matches(&config, &["-l3"], "'3' is not one of 1, 2, 300");
+        assert_parse_error_matches(&config, &["-l300"], "invalid value for <LEVEL:u8>: '300'");
+    }
+
+    #[test]
+    fn conflicts_with() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Quiet, Verbose, Louder }
+
+        let config = Config::new("conflict")
+            .arg(Arg::flag(|| Opt::Quiet).short('q').long("quiet").conflicts_with("verbose"))
+            .arg(Arg::flag(|| Opt::Verbose).short('v').long("verbose"))
+            .arg(Arg::flag(|| Opt::Louder).short('l'));
+
+        assert_parse(&config, &["-q", "-q", "-l"], &[Opt::Quiet, Opt::Quiet, Opt::Louder]);
+        assert_eq!( Err(Error::from_string("conflicts with --verbose").with_option("--quiet")),
+                    parse(&config, &["--verbose", "--quiet"]) );
+        assert_parse_error_matches(&config, &["-q", "-lv"],
+                                   "option -v: conflicts with --quiet");
+    }
+
+    #[test]
+    fn requires() {
+        #[derive(PartialEq, Debug)]
+        enum Opt { Commit, Message(String), Sign, Key }
+
+        let config = Config::new("requires")
+            .arg(Arg::flag(|| Opt::Commit).long("commit").requires("message"))
+            .arg(Arg::str_param("MSG", |s| Ok(Opt::Message(s.to_owned()))).short('m')
+                 .long("message"))
+            .arg(Arg::flag(|| Opt::Sign).long("sign").requires("commit"))
+            .arg(Arg::flag(|| Opt::Key).long("key").requires("sign"));
+
+        assert_eq!( Err(Error::from_string("requires --message").with_option("--commit")),
+                    parse(&config, &["--commit"]) );
+        assert_parse(&config, &["-mhi", "--commit"],
+                     &[Opt::Message("hi".to_owned()), Opt::Commit]);
+        assert_parse(&config, &["--key", "--sign", "--commit", "-m", "x"],
+                     &[Opt::Key, Opt::Sign, Opt::Commit, Opt::Message("x".to_owned())]);
+        assert_parse_error_matches(&config, &["--key", "--sign", "-m", "x"],
+                                   "option --sign: requires --commit");
+        assert_parse_error_matches(&config, &["--key", "--commit"],
+                                   "option --commit: requires --message");
+    }
+
+    #[test]
+    fn error_kinds() {
+        let pos = |args: &[&str]| parse(&pos_config(), args).unwrap_err();
+        let fls = |args: &[&str]| parse(&fls_config(), args).unwrap_err().kind();
+
+        assert_eq!( ErrorKind::UnrecognizedOption, pos(&["-b"]).kind() );
+        assert_eq!( "option -b: unrecognized", pos(&["-b"]).to_string() );
+        assert_eq!( ErrorKind::PositionalNotAccepted, fls(&["x"]) );
+        assert_eq!( ErrorKind::MissingParameter, fls(&["--freq"]) );
+        assert_eq!( ErrorKind::UnexpectedParameter, fls(&["--louder=x"]) );
+        assert_eq!( ErrorKind::ParseFailure, fls(&["-fx"]) );
+        assert_eq!( ErrorKind::Other, Error::from_string("oops").kind() );
+        assert_eq!( ErrorKind::PositionalNotAccepted,
+                    parse(&pos_config().max_positionals(1), &["x", "y"]).unwrap_err().kind() );
+        assert_eq!( ErrorKind::Other, Error::ambiguous(vec!["verbose", "version"]).kind() );
+    }
+
+    #[test]
+    fn wrap_width() {
+        let config = Config::new("wrap")
+            .arg(Arg::flag(|| ()).short('l').long("louder")
+                 .description("Makes the output louder each time this option is given, \
+                               up to a limit of eleven"))
+            .arg(Arg::flag(|| ()).short('x')
+                 .description("a supercalifragilisticexpialidocious word"))
+            .wrap_width(40);
+        let usage = usage(&config);
+
+        assert!( usage.contains("\n  -l, --louder   Makes the output louder\n\
+                                 \x20                each time this option\n\
+                                 \x20                is given, up to a limit\n\
+                                 \x20                of eleven\n") );
+        assert!( usage.contains("\n  -x   a\n       supercalifragilisticexpialidocious\n       word\n") );
+        assert!( super::util::wrap_words("", 10).is_empty() );
+    }
+
+    #[test]
+    fn colored_usage() {
+        assert!( !usage(&fls_config()).contains('\x1b') );
+        assert!( !usage(&fls_config().colored(false)).contains('\x1b') );
+
+        let usage = usage(&fls_config().colored(true));
+        assert!( usage.contains("\x1b[1mUsage:\x1b[0m fls OPTION...") );
+        assert!( usage.contains("\n  \x1b[1m-l, --louder\x1b[0m\n") );
+        assert!( usage.contains("\n  \x1b[1m-f, --freq\x1b[0m <FREQ>") );
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let args = vec!["-x".to_owned()];
+        assert_eq!( Err(1), fls_config().parse_or_help(args, &mut out, &mut err, true) );
+        assert!( String::from_utf8(err).unwrap()
+                 .starts_with("\x1b[31mSyntax error:\x1b[0m option -x: unrecognized\n") );
+    }
+
+    #[test]
+    fn usage_exit_code() {
+        assert_eq!( 1, fls_config().error_exit_code() );
+
+        let config = fls_config().usage_exit_code(64);
+        assert_eq!( 64, config.error_exit_code() );
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        assert_eq!( Err(64), config.parse_or_help(vec!["-x".to_owned()], &mut out, &mut err, false) );
+    }
+
+    #[test]
+    fn parse_lines_skips_blanks_and_comments() {
+        use self::FLS::*;
+
+        let config = fls_config();
+        let input  = "# settings\n\n  -lf 2.5\n\t# indented comment\n--freq '3' -s\n-f \"4\n";
+        let result: Vec<_> = parse_lines(&config, input.as_bytes()).collect();
+
+        assert_eq!( vec![Ok(vec![Louder, Freq(2.5)]),
+                         Ok(vec![Freq(3.0), Softer]),
+                         Err(Error::from_string("unterminated double quote"))],
+                    result );
+    }
+
+    #[allow(non_fmt_panics)]
     fn assert_parse_error_matches<T>(config: &Config<T>, args: &[&str], pattern: &str) {
         match parse(config, args) {
             Ok(_)  => panic!("expected parse failure, got success"),
@@ -221,6 +2019,7 @@ mod tests {
                     Ok(expected) );
     }
 
+    #[allow(clippy::into_iter_on_ref)]
     fn parse<T>(config: &Config<T>, args: &[&str]) -> Result<Vec<T>> {
         let args = args.into_iter().map(ToString::to_string);
         config.iter(args).collect()
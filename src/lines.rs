@@ -0,0 +1,71 @@
+use util::*;
+use super::*;
+
+use std::io;
+
+/// Parses each line read from `reader` as a separate command line.
+///
+/// Each line is split into arguments as a shell would, honoring single and
+/// double quotes and backslash escapes (but performing no expansions), and
+/// parsed with `config`. Blank lines and lines whose first non-blank
+/// character is `#` are skipped.
+///
+/// # Example
+///
+/// ```
+/// use foropts::{Arg, Config};
+///
+/// let config = Config::new("repl")
+///     .arg(Arg::flag(|| "v".to_owned()).short('v'))
+///     .arg(Arg::str_param("FILE", |s| Ok(s.to_owned())));
+///
+/// let input  = "# a comment\n-v 'a file'\n\nother\n";
+/// let result = foropts::parse_lines(&config, input.as_bytes())
+///     .collect::<foropts::Result<Vec<_>>>()
+///     .unwrap();
+///
+/// assert_eq!( vec![vec!["v".to_owned(), "a file".to_owned()],
+///                  vec!["other".to_owned()]],
+///             result );
+/// ```
+pub fn parse_lines<'a, 'b, R, T>(config: &'a Config<'b, T>, reader: R) -> LineIter<'a, 'b, R, T>
+    where R: io::BufRead
+{
+    LineIter {
+        config,
+        lines:      reader.lines(),
+    }
+}
+
+/// The iterator over the parsed lines of input.
+///
+/// Created by [`parse_lines`](fn.parse_lines.html).
+#[derive(Debug)]
+pub struct LineIter<'a, 'b: 'a, R, T: 'a> {
+    config:     &'a Config<'b, T>,
+    lines:      io::Lines<R>,
+}
+
+impl<'a, 'b, R, T> Iterator for LineIter<'a, 'b, R, T>
+    where R: io::BufRead
+{
+    type Item = Result<Vec<T>>;
+
+    fn next(&mut self) -> Option<Result<Vec<T>>> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e)   => return Some(Err(Error::from_string(&e))),
+            };
+
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            return Some(split_args(trimmed)
+                .map_err(|e| Error::from_string(&e))
+                .and_then(|args| self.config.iter(args).collect()));
+        }
+    }
+}
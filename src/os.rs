@@ -0,0 +1,83 @@
+use std::cell::RefCell;
+use std::ffi::{OsStr, OsString};
+use std::rc::Rc;
+
+/// The slot in which [`OsArgs`](struct.OsArgs.html) leaves the raw form of
+/// the argument it last produced, if that was not valid Unicode.
+pub (crate) type RawSlot = Rc<RefCell<Option<OsString>>>;
+
+/// An adaptor from `OsString` arguments to `String`s, for
+/// [`Config::iter_os`](struct.Config.html#method.iter_os).
+///
+/// An argument that is not valid Unicode is produced lossily, for
+/// classification, while its raw form is kept for the action.
+#[derive(Clone, Debug)]
+pub struct OsArgs<J> {
+    args:       J,
+    raw:        RawSlot,
+}
+
+impl<J> OsArgs<J> {
+    pub (crate) fn new(args: J) -> Self {
+        OsArgs {
+            args,
+            raw:        Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub (crate) fn raw_slot(&self) -> RawSlot {
+        self.raw.clone()
+    }
+}
+
+impl<J: Iterator<Item=OsString>> Iterator for OsArgs<J> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let arg = self.args.next()?;
+        let (result, raw) = match arg.into_string() {
+            Ok(s)    => (s, None),
+            Err(raw) => (raw.to_string_lossy().into_owned(), Some(raw)),
+        };
+        *self.raw.borrow_mut() = raw;
+        Some(result)
+    }
+}
+
+/// The part of `raw` from byte `offset` on, provided that the part before
+/// it is valid Unicode and the part after it is not. (If the part after is
+/// valid Unicode, the lossy form of `raw` already holds it exactly.)
+#[cfg(unix)]
+pub (crate) fn raw_suffix(raw: &OsStr, offset: usize) -> Option<OsString> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::str;
+
+    let bytes = raw.as_bytes();
+    if offset > bytes.len() || str::from_utf8(&bytes[.. offset]).is_err() {
+        return None;
+    }
+
+    let suffix = OsStr::from_bytes(&bytes[offset ..]);
+    if suffix.to_str().is_some() {None} else {Some(suffix.to_owned())}
+}
+
+/// The part of `raw` after the first `=`, if any.
+#[cfg(unix)]
+pub (crate) fn raw_after_eq(raw: &OsStr) -> Option<OsString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let ix = raw.as_bytes().iter().position(|&b| b == b'=')?;
+    raw_suffix(raw, ix + 1)
+}
+
+/// Elsewhere an `OsStr` cannot be split, so parameters attached to their
+/// options are taken in their lossy form.
+#[cfg(not(unix))]
+pub (crate) fn raw_suffix(_raw: &OsStr, _offset: usize) -> Option<OsString> {
+    None
+}
+
+#[cfg(not(unix))]
+pub (crate) fn raw_after_eq(_raw: &OsStr) -> Option<OsString> {
+    None
+}
@@ -0,0 +1,90 @@
+use super::*;
+
+/// Parses `args` against a getopt-style `spec` without building a
+/// [`Config`](struct.Config.html) by hand.
+///
+/// Each character of `spec` names a short option; a character followed by
+/// `:` takes a parameter. For example, `"ab:o:"` accepts `-a` as a flag and
+/// `-b` and `-o` with parameters. Each option in `args` is returned with its
+/// parameter, if any. Positional arguments are not accepted.
+///
+/// # Errors
+///
+/// Returns an error if `spec` is malformed or if `args` does not parse.
+///
+/// # Example
+///
+/// ```
+/// let opts = foropts::quick_parse("ab:", &["-a", "-bX"]).unwrap();
+/// assert_eq!( vec![('a', None), ('b', Some("X".to_owned()))], opts );
+/// ```
+pub fn quick_parse(spec: &str, args: &[&str]) -> Result<Vec<(char, Option<String>)>> {
+    let mut config = Config::new("quick_parse");
+    let mut chars  = spec.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == ':' || c == '-' {
+            return Err(Error::from_string(&format!("invalid option spec: {:?}", spec)));
+        }
+
+        let arg = if chars.peek() == Some(&':') {
+            chars.next();
+            Arg::str_param("ARG", move |s| Ok((c, Some(s.to_owned()))))
+        } else {
+            Arg::flag(move || (c, None))
+        };
+
+        config.arg_safe(arg.short(c))?;
+    }
+
+    config.iter(args.iter().map(ToString::to_string)).collect()
+}
+
+/// Parses arguments consisting only of short flags, without building a
+/// [`Config`](struct.Config.html) or any maps.
+///
+/// Returns a bit set in which bit `i` is set if flag `spec[i]` appeared,
+/// along with the positional arguments in order. Flags may be clustered, as
+/// in `-abc`, and `--` ends flag parsing.
+///
+/// # Errors
+///
+/// Returns an error if `spec` has more than 64 flags or if an argument names
+/// a flag not in `spec`.
+///
+/// # Example
+///
+/// ```
+/// let (flags, rest) = foropts::parse_shorts(&['a', 'b', 'c'], &["-ac", "x"]).unwrap();
+/// assert_eq!( 0b101, flags );
+/// assert_eq!( vec!["x"], rest );
+/// ```
+pub fn parse_shorts<'s>(spec: &[char], args: &[&'s str]) -> Result<(u64, Vec<&'s str>)> {
+    if spec.len() > 64 {
+        return Err(Error::from_string("too many flags in spec (max 64)"));
+    }
+
+    let mut flags       = 0;
+    let mut positionals = Vec::new();
+    let mut args        = args.iter();
+
+    while let Some(&arg) = args.next() {
+        if arg == "--" {
+            positionals.extend(args);
+            break;
+        }
+
+        match arg.strip_prefix('-') {
+            Some(cluster) if !cluster.is_empty() => {
+                for c in cluster.chars() {
+                    let index = spec.iter().position(|&f| f == c)
+                        .ok_or_else(|| Error::unknown_flag(format!("-{}", c)))?;
+                    flags |= 1 << index;
+                }
+            }
+            _ => positionals.push(arg),
+        }
+    }
+
+    Ok((flags, positionals))
+}
@@ -0,0 +1,60 @@
+use super::*;
+
+use std::cell::RefCell;
+use std::{fmt, io};
+
+/// Where to read additional arguments from when the marker is seen.
+pub (crate) struct StdinArgs<'a> {
+    marker:     String,
+    reader:     RefCell<Option<Box<dyn io::Read + 'a>>>,
+    args:       RefCell<Option<Result<Vec<String>>>>,
+}
+
+impl<'a> fmt::Debug for StdinArgs<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StdinArgs")
+            .field("marker",    &self.marker)
+            .field("reader",    &self.reader.borrow().as_ref().map(|_| "…"))
+            .field("args",      &self.args.borrow())
+            .finish()
+    }
+}
+
+impl<'a> StdinArgs<'a> {
+    pub (crate) fn new() -> Self {
+        StdinArgs {
+            marker:     "-".to_owned(),
+            reader:     RefCell::new(Some(Box::new(io::stdin()))),
+            args:       RefCell::new(None),
+        }
+    }
+
+    pub (crate) fn marker(&self) -> &str {
+        &self.marker
+    }
+
+    pub (crate) fn set_marker(&mut self, marker: String) {
+        self.marker = marker;
+    }
+
+    pub (crate) fn set_reader(&mut self, reader: Box<dyn io::Read + 'a>) {
+        self.reader = RefCell::new(Some(reader));
+        self.args   = RefCell::new(None);
+    }
+
+    /// Reads whitespace-separated arguments the first time, and returns the
+    /// same arguments, or the same error, every time after.
+    pub (crate) fn read(&self) -> Result<Vec<String>> {
+        self.args.borrow_mut().get_or_insert_with(|| self.read_reader()).clone()
+    }
+
+    fn read_reader(&self) -> Result<Vec<String>> {
+        let mut input = String::new();
+        if let Some(mut reader) = self.reader.borrow_mut().take() {
+            reader.read_to_string(&mut input)
+                .map_err(|e| Error::from_string(&e).with_option(self.marker.as_str()))?;
+        }
+
+        Ok(input.split_whitespace().map(ToOwned::to_owned).collect())
+    }
+}
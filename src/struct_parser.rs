@@ -0,0 +1,109 @@
+use super::*;
+
+use std::fmt;
+use std::rc::Rc;
+
+/// An update to the struct being populated.
+type Setter<'a, S> = Box<dyn FnOnce(&mut S) + 'a>;
+
+/// Populates the fields of a struct from the arguments, without writing the
+/// loop that matches each result.
+///
+/// Each option is registered by its long name along with a setter for the
+/// field it controls; [`run`](#method.run) starts from `S::default()` and
+/// applies the setters in the order the options appear.
+///
+/// # Parameters
+///
+/// `<'a>` – The lifetime of the setters
+///
+/// `<S>`  – The struct being populated
+///
+/// # Example
+///
+/// ```
+/// use foropts::StructParser;
+///
+/// #[derive(Default)]
+/// struct Opts {
+///     verbose: bool,
+///     out:     String,
+/// }
+///
+/// let parser = StructParser::new("example")
+///     .flag("verbose", |s: &mut Opts, v| s.verbose = v)
+///     .value("out", |s: &mut Opts, v| s.out = v);
+///
+/// let opts = parser.run(vec!["--out".to_owned(), "x".to_owned(),
+///                            "--verbose".to_owned()]).unwrap();
+/// assert!( opts.verbose );
+/// assert_eq!( "x", opts.out );
+/// ```
+pub struct StructParser<'a, S> {
+    config:     Config<'a, Setter<'a, S>>,
+}
+
+impl<'a, S> fmt::Debug for StructParser<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StructParser")
+            .field("config",    &"…")
+            .finish()
+    }
+}
+
+impl<'a, S: 'a> StructParser<'a, S> {
+    /// Creates a new `StructParser` given the name of the program.
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        StructParser {
+            config:     Config::new(name),
+        }
+    }
+
+    /// Registers a Boolean flag `--name`, whose setter is called with `true`
+    /// when the flag is given.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `--name` is already registered.
+    pub fn flag<F>(mut self, name: &str, setter: F) -> Self
+        where F: Fn(&mut S, bool) + 'a
+    {
+        let setter = Rc::new(setter);
+        self.config = self.config.arg(Arg::flag(move || {
+            let setter = setter.clone();
+            Box::new(move |s: &mut S| setter(s, true)) as Setter<S>
+        }).long(name));
+        self
+    }
+
+    /// Registers an option `--name` whose parameter is parsed by `str::parse`
+    /// and passed to the setter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `--name` is already registered.
+    pub fn value<A, F>(mut self, name: &str, setter: F) -> Self
+        where F: Fn(&mut S, A) + 'a,
+              A: FromStr + 'a,
+              A::Err: ToString
+    {
+        let setter = Rc::new(setter);
+        self.config = self.config.arg(Arg::parsed_param(name.to_uppercase(), move |value: A| {
+            let setter = setter.clone();
+            Box::new(move |s: &mut S| setter(s, value)) as Setter<S>
+        }).long(name));
+        self
+    }
+
+    /// Parses `args` into a struct that starts as `S::default()`.
+    pub fn run<I>(&self, args: I) -> Result<S>
+        where I: IntoIterator<Item=String>,
+              S: Default
+    {
+        let mut result = S::default();
+        for setter in self.config.iter(args) {
+            setter?(&mut result);
+        }
+        Ok(result)
+    }
+}
@@ -1,10 +1,193 @@
+use std::mem;
+
 /// Returns `None`, or `Some` of a non-empty string.
 pub fn non_empty_string(s: &str) -> Option<&str> {
     if s.is_empty() {None} else {Some(s)}
 }
 
+/// The ANSI style code for bold text.
+pub const BOLD: &str = "1";
+
+/// The ANSI style code for red text.
+pub const RED: &str = "31";
+
+/// Wraps `s` in the ANSI escape codes for `style` if `enabled`.
+pub fn styled(s: &str, style: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", style, s)
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Splits `text` into lines of at most `width` characters, breaking only
+/// between words. A word longer than `width` gets a line of its own.
+pub fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line  = String::new();
+    let mut len   = 0;
+
+    for word in text.split_whitespace() {
+        let word_len = word.chars().count();
+        if len > 0 && len + 1 + word_len > width {
+            lines.push(mem::take(&mut line));
+            len = 0;
+        }
+        if len > 0 {
+            line.push(' ');
+            len += 1;
+        }
+        line.push_str(word);
+        len += word_len;
+    }
+
+    if len > 0 {
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Whether `s` is `-` followed by a digit, and parses as a number.
+pub fn is_negative_number(s: &str) -> bool {
+    let digit = match s.strip_prefix('-') {
+        Some(rest) => rest.starts_with(|c: char| c.is_ascii_digit()),
+        None       => false,
+    };
+    digit && s.parse::<f64>().is_ok()
+}
+
 /// Like `split_first` but for `&str`.
 pub fn split_first_str(s: &str) -> Option<(char, &str)> {
     let mut chars = s.chars();
     chars.next().map(|c| (c, chars.as_str()))
 }
+
+/// Expands `$NAME` and `${NAME}` references in `s` using `lookup`, with `$$`
+/// standing for a literal `$`. Returns the name of the first variable for
+/// which `lookup` returns `None`.
+pub fn expand_vars<F>(s: &str, lookup: F) -> Result<String, String>
+    where F: Fn(&str) -> Option<String>
+{
+    let mut result = String::with_capacity(s.len());
+    let mut rest   = s;
+
+    while let Some(ix) = rest.find('$') {
+        result.push_str(&rest[.. ix]);
+        let after = &rest[ix + 1 ..];
+
+        let (name, next) = if let Some(escaped) = after.strip_prefix('$') {
+            result.push('$');
+            rest = escaped;
+            continue;
+        } else if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[.. end], &braced[end + 1 ..]),
+                None      => ("", after),
+            }
+        } else {
+            let end = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[.. end], &after[end ..])
+        };
+
+        if name.is_empty() {
+            result.push('$');
+            rest = after;
+        } else {
+            result.push_str(&lookup(name).ok_or_else(|| name.to_owned())?);
+            rest = next;
+        }
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Shortens a type name as given by `std::any::type_name` by dropping the
+/// module paths, so that `alloc::vec::Vec<alloc::string::String>` becomes
+/// `Vec<String>`.
+pub fn short_type_name(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut start  = 0;
+
+    for (ix, c) in name.char_indices() {
+        if !(c.is_alphanumeric() || c == '_' || c == ':') {
+            result.push_str(last_path_segment(&name[start .. ix]));
+            result.push(c);
+            start = ix + c.len_utf8();
+        }
+    }
+
+    result.push_str(last_path_segment(&name[start ..]));
+    result
+}
+
+fn last_path_segment(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+/// Splits `s` into arguments the way a POSIX shell would, without
+/// expansions: whitespace separates arguments, single quotes preserve
+/// everything up to the closing quote, and within double quotes or bare
+/// words a backslash escapes the next character. Returns an error message for
+/// an unterminated quote or a trailing backslash.
+pub fn split_args(s: &str) -> Result<Vec<String>, String> {
+    let mut result  = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars   = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c)    => current.push(c),
+                        None       => return Err("unterminated single quote".to_owned()),
+                    }
+                }
+            }
+
+            '"'  => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"')  => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => current.push(c),
+                            None    => return Err("unterminated double quote".to_owned()),
+                        },
+                        Some(c)    => current.push(c),
+                        None       => return Err("unterminated double quote".to_owned()),
+                    }
+                }
+            }
+
+            '\\' => {
+                in_word = true;
+                current.push(chars.next().ok_or_else(|| "trailing backslash".to_owned())?);
+            }
+
+            c if c.is_whitespace() => {
+                if in_word {
+                    result.push(mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+
+            c    => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        result.push(current);
+    }
+
+    Ok(result)
+}